@@ -1,5 +1,5 @@
 use amethyst::assets::{Handle, Loader};
-use amethyst::core::math::{Matrix3, Vector3};
+use amethyst::core::math::Vector3;
 use amethyst::core::shrev::{EventChannel, ReaderId};
 use amethyst::core::ecs::world::Builder;
 use amethyst::core::ecs::Join;
@@ -16,9 +16,7 @@ use amethyst::{
 use nphysics_ecs_dumb::ncollide::shape::{Ball, ShapeHandle};
 use nphysics_ecs_dumb::nphysics::material::BasicMaterial as PhysicsMaterial;
 use nphysics_ecs_dumb::nphysics::math::Velocity;
-use nphysics_ecs_dumb::nphysics::volumetric::Volumetric;
 use nphysics_ecs_dumb::*;
-use num_traits::identities::One;
 use std::time::Duration;
 
 #[derive(Default)]
@@ -90,6 +88,10 @@ impl SimpleState for GameState {
 
         let ball = ShapeHandle::new(Ball::new(1.0));
 
+        // Mass properties of the sphere are computed from its collider.
+        let mut ball_body = DynamicBody::new_rigidbody_from_colliders();
+        ball_body.velocity = Velocity::linear(0.0, 1.0, 0.0);
+
         // Add Sphere (todo: add many, add rigidbodies and colliders)
         data.world
             .create_entity()
@@ -97,12 +99,7 @@ impl SimpleState for GameState {
             .with(material.clone())
             .with(Transform::from(Vector3::new(0.0, 15.0, -10.0)))
             .with(GlobalTransform::default())
-            .with(DynamicBody::new_rigidbody_with_velocity(
-                Velocity::linear(0.0, 1.0, 0.0),
-                10.0,
-                Matrix3::one(),
-                ball.center_of_mass(),
-            ))
+            .with(ball_body)
             .with(
                 ColliderBuilder::from(ball.clone())
                     .physics_material(PhysicsMaterial::default())
@@ -167,12 +164,15 @@ impl SimpleState for GameState {
         event: StateEvent,
     ) -> SimpleTrans {
         if let StateEvent::Window(event) = &event {
-            for _ in data
+            for event in data
                 .world
                 .read_resource::<EventChannel<EntityContactEvent>>()
                 .read(self.collision_reader.as_mut().unwrap())
             {
-                println!("Collision Event Detected.");
+                println!(
+                    "Collision Event Detected: {:?}, impulse: {}",
+                    event.state, event.impulse
+                );
             }
 
             // Exit if user hits Escape or closes the window
@@ -0,0 +1,105 @@
+//! Headless example of moons orbiting a point attractor, without global gravity.
+//!
+//! The radial field pulls with an acceleration of `STRENGTH / distance²`, so a moon launched
+//! perpendicularly at `sqrt(STRENGTH / distance)` stays on a circular orbit. The position of each
+//! moon is printed regularly: the inner ones go around faster.
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::Vector3;
+use amethyst::core::{GlobalTransform, Transform, TransformBundle};
+use amethyst::{
+    Application, GameData, GameDataBuilder, SimpleState, SimpleTrans, StateData, Trans,
+};
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point, Velocity};
+use nphysics_ecs_dumb::*;
+
+const STRENGTH: f32 = 100.0;
+/// Distance of each moon to the attractor.
+const ORBITS: [f32; 3] = [5.0, 10.0, 20.0];
+/// Number of frames to simulate, printing the moons every `PRINT_EVERY`.
+const FRAMES: u32 = 1200;
+const PRINT_EVERY: u32 = 60;
+
+#[derive(Default)]
+struct OrbitState {
+    frames: u32,
+    moons: Vec<Entity>,
+}
+
+impl SimpleState for OrbitState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        data.world.add_resource(Gravity::zeros());
+
+        data.world
+            .create_entity()
+            .with(Transform::default())
+            .with(GlobalTransform::default())
+            .with(GravityField::new(GravityFieldKind::Radial {
+                strength: STRENGTH,
+                falloff: 2.0,
+                min_distance: 0.5,
+            }))
+            .build();
+
+        for radius in ORBITS.iter() {
+            let speed = (STRENGTH / radius).sqrt();
+            let moon = data
+                .world
+                .create_entity()
+                .with(Transform::from(Vector3::new(*radius, 0.0, 0.0)))
+                .with(GlobalTransform::default())
+                .with(DynamicBody::new_rigidbody_with_velocity(
+                    Velocity::linear(0.0, 0.0, speed),
+                    1.0,
+                    AngularInertia::identity(),
+                    Point::origin(),
+                ))
+                .build();
+            self.moons.push(moon);
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+
+        if self.frames % PRINT_EVERY == 0 {
+            let transforms = data.world.read_storage::<Transform>();
+            let positions = self
+                .moons
+                .iter()
+                .filter_map(|moon| transforms.get(*moon))
+                .map(|transform| {
+                    let position = transform.translation();
+                    format!(
+                        "({:7.2}, {:7.2}) {:4.0}°",
+                        position.x,
+                        position.z,
+                        position.z.atan2(position.x).to_degrees()
+                    )
+                })
+                .collect::<Vec<_>>();
+            println!("Frame {:5}: {}", self.frames, positions.join("  "));
+        }
+
+        if self.frames < FRAMES {
+            Trans::None
+        } else {
+            Trans::Quit
+        }
+    }
+}
+
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let game_data = GameDataBuilder::default()
+        .with_bundle(TransformBundle::new())?
+        .with_bundle(PhysicsBundle::new().with_dep(&["transform_system"]))?;
+
+    let assets_dir = format!("{}/examples/assets", env!("CARGO_MANIFEST_DIR"));
+    let mut application = Application::new(assets_dir, OrbitState::default(), game_data)?;
+    application.run();
+
+    Ok(())
+}
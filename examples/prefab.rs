@@ -0,0 +1,79 @@
+//! Headless example loading physics objects from `assets/prefab/physics.ron`.
+
+use amethyst::assets::{PrefabLoader, PrefabLoaderSystem, RonFormat};
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Join;
+use amethyst::core::{Transform, TransformBundle};
+use amethyst::derive::PrefabData;
+use amethyst::{
+    Application, GameData, GameDataBuilder, SimpleState, SimpleTrans, StateData, Trans,
+};
+use nphysics_ecs_dumb::*;
+use serde::Deserialize;
+
+/// Number of frames to simulate before printing the state of the loaded entities.
+const FRAMES: u32 = 120;
+
+#[derive(Default, Deserialize, PrefabData)]
+#[serde(default)]
+struct ScenePrefabData {
+    transform: Option<Transform>,
+    physics: Option<PhysicsPrefab>,
+}
+
+#[derive(Default)]
+struct LoadState {
+    frames: u32,
+}
+
+impl SimpleState for LoadState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let handle = data
+            .world
+            .exec(|loader: PrefabLoader<'_, ScenePrefabData>| {
+                loader.load("prefab/physics.ron", RonFormat, (), ())
+            });
+        data.world.create_entity().with(handle).build();
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+        if self.frames < FRAMES {
+            return Trans::None;
+        }
+
+        let transforms = data.world.read_storage::<Transform>();
+        let bodies = data.world.read_storage::<DynamicBody>();
+        let colliders = data.world.read_storage::<Collider>();
+
+        for (transform, body, collider) in (&transforms, (&bodies).maybe(), &colliders).join() {
+            println!(
+                "{:?} at {}, mass: {:?}",
+                collider.shape,
+                transform.translation(),
+                body.map(|body| body.mass)
+            );
+        }
+
+        Trans::Quit
+    }
+}
+
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let game_data = GameDataBuilder::default()
+        .with(
+            PrefabLoaderSystem::<ScenePrefabData>::default(),
+            "scene_loader",
+            &[],
+        )
+        .with_bundle(TransformBundle::new().with_dep(&["scene_loader"]))?
+        .with_bundle(PhysicsBundle::new().with_dep(&["transform_system"]))?;
+
+    let assets_dir = format!("{}/examples/assets", env!("CARGO_MANIFEST_DIR"));
+    let mut application = Application::new(assets_dir, LoadState::default(), game_data)?;
+    application.run();
+
+    Ok(())
+}
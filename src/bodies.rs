@@ -1,26 +1,221 @@
+use crate::colliders::Collider;
+use crate::shapes::combine_mass_properties;
 use amethyst::ecs::{Component, FlaggedStorage};
-use nalgebra::Matrix3;
-use nphysics3d::math::{Force, Point, Velocity};
-use nphysics3d::object::{BodyHandle, BodyStatus};
+use nalgebra::{Isometry3, Matrix3, Vector3};
+use nphysics3d::algebra::ForceType;
+use nphysics3d::math::{AngularVector, Force, Inertia, Point, Vector, Velocity};
+use nphysics3d::object::{ActivationStatus, Body, BodyHandle, BodyStatus, RigidBody};
+use std::f32::EPSILON;
 
 /// Rigid physics body, for use in `PhysicsBody` Component.
-/// Currently only the velocity is read and updated at runtime.
-/// The properties of mass are only written at physics body creation time.
+/// The velocity is read and updated at runtime.
+/// The properties of mass are written at physics body creation time and whenever the component is
+/// modified.
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, new)]
 pub struct DynamicBody {
     #[serde(skip)]
     #[new(default)]
     pub(crate) handle: Option<BodyHandle>,
+    #[serde(default = "Velocity::zero")]
     pub velocity: Velocity<f32>,
 
-    // TODO: update these in the physics system below.
+    /// Where the mass properties below come from.
+    #[serde(default)]
+    #[new(default)]
+    pub mass_source: MassSource,
+    /// Mass of the body. Must be strictly positive.
     pub mass: f32,
+    /// Angular inertia tensor of the body, expressed in the body's local space. Must be symmetric.
+    #[serde(default = "Matrix3::identity")]
     pub angular_mass: Matrix3<f32>,
+    /// Center of mass of the body, expressed in the body's local space.
+    #[serde(default = "Point::origin")]
     pub center_of_mass: Point<f32>,
 
+    /// Force and torque applied at the center of mass on every physics step, until cleared by the
+    /// `PhysicsStepperSystem` after it has stepped the world.
+    #[serde(default = "Force::zero")]
     pub external_forces: Force<f32>,
-    #[serde(skip)]
+    /// Impulse and angular impulse applied at the center of mass once, on the next physics step.
+    #[serde(default = "Force::zero")]
+    #[new(value = "Force::zero()")]
+    pub external_impulses: Force<f32>,
+    /// Sum of continuous forces applied at world points, and the sum of their moments around the
+    /// world origin. Converted to a force and torque at the center of mass when applied.
+    #[serde(default = "Force::zero")]
+    #[new(value = "Force::zero()")]
+    pub(crate) external_point_forces: Force<f32>,
+    /// Same as `external_point_forces`, for impulses.
+    #[serde(default = "Force::zero")]
+    #[new(value = "Force::zero()")]
+    pub(crate) external_point_impulses: Force<f32>,
+    /// Fraction of the linear velocity lost every second.
+    #[serde(default)]
+    #[new(default)]
+    pub linear_damping: f32,
+    /// Fraction of the angular velocity lost every second.
+    #[serde(default)]
+    #[new(default)]
+    pub angular_damping: f32,
+    /// Maximum linear velocity of the body, unbounded if `None`.
+    #[serde(default)]
+    #[new(default)]
+    pub max_linear_velocity: Option<f32>,
+    /// Maximum angular velocity of the body, unbounded if `None`.
+    #[serde(default)]
+    #[new(default)]
+    pub max_angular_velocity: Option<f32>,
+    /// Kinetic energy per unit of mass below which the body falls asleep. The body never sleeps if
+    /// `None`.
+    #[serde(default = "default_sleep_threshold")]
+    #[new(value = "default_sleep_threshold()")]
+    pub sleep_threshold: Option<f32>,
+    /// World axes along which the body can't move.
+    #[serde(default)]
+    #[new(default)]
+    pub locked_translations: AxisLocks,
+    /// World axes around which the body can't rotate.
+    #[serde(default)]
+    #[new(default)]
+    pub locked_rotations: AxisLocks,
+    /// Whether the motion of the body is checked for tunneling before every physics step. Enable
+    /// it for small and fast bodies, like bullets, that could otherwise go through thin colliders.
+    /// Only the linear motion is checked, not the rotation.
+    #[serde(default)]
+    #[new(default)]
+    pub ccd_enabled: bool,
+    /// Distance the body must travel during a physics step for its motion to be checked when
+    /// `ccd_enabled` is set. Slower bodies can't tunnel, and skip the check.
+    #[serde(default)]
+    #[new(default)]
+    pub ccd_motion_threshold: f32,
+    /// Multiplier of the world gravity for this body: 0 makes it float, negative values make it
+    /// rise.
+    #[serde(default = "default_gravity_scale")]
+    #[new(value = "1.0")]
+    pub gravity_scale: f32,
+    /// Whether gravity affects this body at all.
+    #[serde(default = "default_gravity_enabled")]
+    #[new(value = "true")]
+    pub gravity_enabled: bool,
+    /// Whether the body is dynamic, kinematic, static or disabled. Can be changed at runtime.
+    #[serde(with = "BodyStatusDef", default = "default_body_status")]
     pub body_status: BodyStatus,
+    /// Pose last written to the transforms by the `SyncBodiesFromPhysicsSystem`. Transforms still
+    /// matching it haven't been moved by the user and aren't pushed back to the physics world.
+    #[serde(skip)]
+    #[new(default)]
+    pub(crate) synced_position: Option<Isometry3<f32>>,
+    /// Pose a kinematic body has to reach by the end of the next physics steps, taken from its
+    /// transform. The `PhysicsStepperSystem` gives the body the velocity needed to get there.
+    #[serde(skip)]
+    #[new(default)]
+    pub(crate) kinematic_target: Option<Isometry3<f32>>,
+    /// Whether nphysics put the body to sleep, updated by the `SyncBodiesFromPhysicsSystem`.
+    #[serde(skip)]
+    #[new(default)]
+    pub(crate) sleeping: bool,
+    /// Wake up or sleep request, applied by the `SyncBodiesToPhysicsSystem`.
+    #[serde(skip)]
+    #[new(default)]
+    pub(crate) activation_request: Option<ActivationRequest>,
+}
+
+/// Change of the activation of a body requested through `DynamicBody::wake_up` or
+/// `DynamicBody::sleep`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ActivationRequest {
+    Wake,
+    Sleep,
+}
+
+/// Mirror of `BodyStatus` for serde.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "BodyStatus")]
+enum BodyStatusDef {
+    Disabled,
+    Static,
+    Dynamic,
+    Kinematic,
+}
+
+fn default_body_status() -> BodyStatus {
+    BodyStatus::Dynamic
+}
+
+fn default_sleep_threshold() -> Option<f32> {
+    Some(ActivationStatus::default_threshold())
+}
+
+fn default_gravity_scale() -> f32 {
+    1.0
+}
+
+fn default_gravity_enabled() -> bool {
+    true
+}
+
+/// Axes along or around which a `DynamicBody` can't move. Enforced by nphysics, which stops
+/// applying forces to the locked degrees of freedom while their velocity is kept at zero.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, new)]
+pub struct AxisLocks {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+}
+
+impl AxisLocks {
+    pub fn all() -> Self {
+        AxisLocks::new(true, true, true)
+    }
+
+    pub fn none() -> Self {
+        AxisLocks::default()
+    }
+
+    pub fn as_vector(&self) -> Vector3<bool> {
+        Vector3::new(self.x, self.y, self.z)
+    }
+
+    /// Sets the components of the vector on locked axes to zero.
+    pub fn clear_locked(&self, vector: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            if self.x { 0.0 } else { vector.x },
+            if self.y { 0.0 } else { vector.y },
+            if self.z { 0.0 } else { vector.z },
+        )
+    }
+}
+
+/// Where the mass properties of a `DynamicBody` come from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MassSource {
+    /// The mass properties set on the component are used as is.
+    Explicit,
+    /// The mass properties are computed from the shapes and densities of the colliders attached to
+    /// the body, and written back to the component whenever those colliders change.
+    FromColliders,
+}
+
+impl Default for MassSource {
+    fn default() -> Self {
+        MassSource::Explicit
+    }
+}
+
+/// How a force added to a `DynamicBody` is applied to the physics world.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForceMode {
+    /// Applied as a force on every physics step of the current frame.
+    Continuous,
+    /// Applied once as an impulse on the next physics step.
+    Instantaneous,
+}
+
+impl Default for ForceMode {
+    fn default() -> Self {
+        ForceMode::Continuous
+    }
 }
 
 impl DynamicBody {
@@ -32,11 +227,30 @@ impl DynamicBody {
         DynamicBody {
             handle: None,
             velocity: Velocity::<f32>::zero(),
+            mass_source: MassSource::Explicit,
             mass,
             angular_mass,
             center_of_mass,
             external_forces: Force::<f32>::zero(),
+            external_impulses: Force::<f32>::zero(),
+            external_point_forces: Force::<f32>::zero(),
+            external_point_impulses: Force::<f32>::zero(),
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            max_linear_velocity: None,
+            max_angular_velocity: None,
+            sleep_threshold: default_sleep_threshold(),
+            locked_translations: AxisLocks::default(),
+            locked_rotations: AxisLocks::default(),
+            ccd_enabled: false,
+            ccd_motion_threshold: 0.0,
+            gravity_scale: 1.0,
+            gravity_enabled: true,
             body_status: BodyStatus::Dynamic,
+            synced_position: None,
+            kinematic_target: None,
+            sleeping: false,
+            activation_request: None,
         }
     }
 
@@ -47,19 +261,294 @@ impl DynamicBody {
         center_of_mass: Point<f32>,
     ) -> Self {
         DynamicBody {
-            handle: None,
             velocity,
-            mass,
-            angular_mass,
-            center_of_mass,
-            external_forces: Force::<f32>::zero(),
-            body_status: BodyStatus::Dynamic,
+            ..DynamicBody::new_rigidbody(mass, angular_mass, center_of_mass)
+        }
+    }
+
+    /// Creates a rigid body whose mass properties are computed from its colliders.
+    pub fn new_rigidbody_from_colliders() -> Self {
+        DynamicBody {
+            mass_source: MassSource::FromColliders,
+            ..DynamicBody::new_rigidbody(1.0, Matrix3::identity(), Point::origin())
+        }
+    }
+
+    /// Creates a static body, that never moves.
+    pub fn new_static() -> Self {
+        DynamicBody {
+            body_status: BodyStatus::Static,
+            ..DynamicBody::new_rigidbody(1.0, Matrix3::identity(), Point::origin())
+        }
+    }
+
+    /// Creates a kinematic body, moved by the physics world along with its transform. It pushes
+    /// dynamic bodies on its way, but isn't affected by them.
+    pub fn new_kinematic() -> Self {
+        DynamicBody {
+            body_status: BodyStatus::Kinematic,
+            ..DynamicBody::new_rigidbody(1.0, Matrix3::identity(), Point::origin())
         }
     }
 
     pub fn handle(&self) -> Option<BodyHandle> {
         self.handle
     }
+
+    /// Whether the body is asleep, i.e. nphysics stopped simulating it until something touches it.
+    /// Sleeping bodies keep their transform, and their velocity is zero.
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Wakes the body up on the next physics update. Cancels a pending `sleep` request.
+    pub fn wake_up(&mut self) {
+        self.activation_request = Some(ActivationRequest::Wake);
+    }
+
+    /// Puts the body to sleep on the next physics update, stopping it. It is woken up again by
+    /// contacts with awake bodies, or by `wake_up`. Cancels a pending `wake_up` request.
+    pub fn sleep(&mut self) {
+        self.activation_request = Some(ActivationRequest::Sleep);
+    }
+
+    /// Adds a force and torque acting on the center of mass of this body.
+    pub fn add_force(&mut self, force: Force<f32>, mode: ForceMode) {
+        match mode {
+            ForceMode::Continuous => self.external_forces += force,
+            ForceMode::Instantaneous => self.external_impulses += force,
+        }
+    }
+
+    /// Adds a force acting on the given point of this body, expressed in world space.
+    /// A force not going through the center of mass will also make the body rotate.
+    pub fn add_force_at_point(&mut self, force: Vector<f32>, point: Point<f32>, mode: ForceMode) {
+        let moment = Force::new(force, point.coords.cross(&force));
+        match mode {
+            ForceMode::Continuous => self.external_point_forces += moment,
+            ForceMode::Instantaneous => self.external_point_impulses += moment,
+        }
+    }
+
+    /// Adds a continuous linear force acting on the center of mass of this body.
+    pub fn apply_force(&mut self, force: Vector<f32>) {
+        self.add_force(Force::linear(force), ForceMode::Continuous);
+    }
+
+    /// Adds a continuous torque to this body.
+    pub fn apply_torque(&mut self, torque: AngularVector<f32>) {
+        self.add_force(Force::torque(torque), ForceMode::Continuous);
+    }
+
+    /// Adds a one-shot linear impulse acting on the center of mass of this body.
+    pub fn apply_impulse(&mut self, impulse: Vector<f32>) {
+        self.add_force(Force::linear(impulse), ForceMode::Instantaneous);
+    }
+
+    /// Adds a one-shot angular impulse to this body.
+    pub fn apply_angular_impulse(&mut self, impulse: AngularVector<f32>) {
+        self.add_force(Force::torque(impulse), ForceMode::Instantaneous);
+    }
+
+    /// Whether any force or impulse is waiting to be applied to this body.
+    pub fn has_pending_forces(&self) -> bool {
+        let is_zero = |force: &Force<f32>| force.as_vector().iter().all(|value| *value == 0.0);
+        !is_zero(&self.external_forces)
+            || !is_zero(&self.external_impulses)
+            || !is_zero(&self.external_point_forces)
+            || !is_zero(&self.external_point_impulses)
+    }
+
+    /// Removes all pending forces and impulses from this body.
+    pub fn clear_forces(&mut self) {
+        self.external_forces = Force::zero();
+        self.external_impulses = Force::zero();
+        self.external_point_forces = Force::zero();
+        self.external_point_impulses = Force::zero();
+    }
+
+    /// Applies the pending forces to the rigid body for a single physics step. Impulses are only
+    /// applied if this is the first step of the frame.
+    pub(crate) fn apply_pending_forces(&self, rigid_body: &mut RigidBody<f32>, first_step: bool) {
+        let center_of_mass = rigid_body.center_of_mass();
+        let at_center_of_mass = |point_forces: &Force<f32>| {
+            Force::new(
+                point_forces.linear,
+                point_forces.angular - center_of_mass.coords.cross(&point_forces.linear),
+            )
+        };
+
+        let force = self.external_forces + at_center_of_mass(&self.external_point_forces);
+        rigid_body.apply_force(0, &force, ForceType::Force, true);
+
+        if first_step {
+            let impulse = self.external_impulses + at_center_of_mass(&self.external_point_impulses);
+            rigid_body.apply_force(0, &impulse, ForceType::Impulse, true);
+        }
+    }
+
+    /// The given velocity, without its components on the locked axes of this body.
+    pub fn locked_velocity(&self, velocity: &Velocity<f32>) -> Velocity<f32> {
+        Velocity::new(
+            self.locked_translations.clear_locked(&velocity.linear),
+            self.locked_rotations.clear_locked(&velocity.angular),
+        )
+    }
+
+    /// Pushes the damping, velocity limits, sleep threshold and axis locks of this body to the
+    /// rigid body.
+    pub(crate) fn apply_motion_properties_to(&self, rigid_body: &mut RigidBody<f32>) {
+        rigid_body.set_linear_damping(self.linear_damping);
+        rigid_body.set_angular_damping(self.angular_damping);
+        rigid_body.set_max_linear_velocity(self.max_linear_velocity.unwrap_or(std::f32::MAX));
+        rigid_body.set_max_angular_velocity(self.max_angular_velocity.unwrap_or(std::f32::MAX));
+        rigid_body.set_deactivation_threshold(self.sleep_threshold);
+        rigid_body.set_translations_kinematic(self.locked_translations.as_vector());
+        rigid_body.set_rotations_kinematic(self.locked_rotations.as_vector());
+        let velocity = self.locked_velocity(rigid_body.velocity());
+        rigid_body.set_velocity(velocity);
+    }
+
+    /// Applies and clears the pending wake up or sleep request of this body.
+    pub(crate) fn apply_activation_request_to(&mut self, rigid_body: &mut RigidBody<f32>) {
+        match self.activation_request.take() {
+            Some(ActivationRequest::Wake) => {
+                trace!("Waking up body {:?}", self.handle);
+                rigid_body.activate();
+            }
+            Some(ActivationRequest::Sleep) => {
+                trace!("Putting body {:?} to sleep", self.handle);
+                rigid_body.deactivate();
+                self.velocity = Velocity::zero();
+            }
+            None => {}
+        }
+    }
+
+    /// Whether the gravity of the physics world applies to this body as is. Otherwise nphysics
+    /// ignores gravity for this body, and the `PhysicsStepperSystem` applies the scaled gravity.
+    pub(crate) fn uses_world_gravity(&self) -> bool {
+        self.gravity_enabled && self.gravity_scale == 1.0
+    }
+
+    /// Gravity this body is subject to, given the gravity of the physics world.
+    pub fn gravity(&self, world_gravity: &Vector<f32>) -> Vector<f32> {
+        if self.gravity_enabled {
+            world_gravity * self.gravity_scale
+        } else {
+            Vector::zeros()
+        }
+    }
+
+    /// Applies the scaled gravity to the rigid body for a single physics step, if nphysics doesn't
+    /// apply gravity to it already.
+    pub(crate) fn apply_scaled_gravity(
+        &self,
+        rigid_body: &mut RigidBody<f32>,
+        gravity: &Vector<f32>,
+    ) {
+        if self.uses_world_gravity() || !self.gravity_enabled || self.gravity_scale == 0.0 {
+            return;
+        }
+        let acceleration = Force::linear(self.gravity(gravity));
+        rigid_body.apply_force(0, &acceleration, ForceType::AccelerationChange, false);
+    }
+
+    /// Gives a kinematic rigid body the velocity needed to reach its target pose in `time`.
+    pub(crate) fn apply_kinematic_velocity_to(&self, rigid_body: &mut RigidBody<f32>, time: f32) {
+        let target = match self.kinematic_target {
+            Some(target) => target,
+            None => return,
+        };
+        let current = *rigid_body.position();
+
+        // nphysics moves the center of mass along the linear velocity and rotates around it.
+        let center_of_mass = rigid_body.center_of_mass();
+        let target_center_of_mass = target * current.inverse_transform_point(&center_of_mass);
+        let rotation = target.rotation * current.rotation.inverse();
+
+        rigid_body.set_velocity(Velocity::new(
+            (target_center_of_mass - center_of_mass) / time,
+            rotation.scaled_axis() / time,
+        ));
+    }
+
+    /// Sets the mass properties of this body to the combined mass properties of the given
+    /// colliders. Returns `false` and leaves the body untouched if none of the colliders has a
+    /// volume.
+    pub fn set_mass_properties_from<'c>(
+        &mut self,
+        colliders: impl IntoIterator<Item = &'c Collider>,
+    ) -> bool {
+        let parts = colliders
+            .into_iter()
+            .filter_map(Collider::mass_properties)
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            return false;
+        }
+
+        let (mass, center_of_mass, angular_mass) = combine_mass_properties(&parts);
+        self.mass = mass;
+        self.center_of_mass = center_of_mass;
+        self.angular_mass = angular_mass;
+        true
+    }
+
+    /// Pushes the mass properties of this body to the rigid body, if they are valid.
+    pub(crate) fn apply_mass_properties_to(&self, rigid_body: &mut RigidBody<f32>) {
+        match self.validate_mass_properties() {
+            Ok(()) => {
+                rigid_body.set_local_inertia(Inertia::new(self.mass, self.angular_mass));
+                rigid_body.set_local_center_of_mass(self.center_of_mass);
+            }
+            Err(err) => error!(
+                "Invalid mass properties for body with handle {:?}, keeping previous values: {}",
+                rigid_body.handle(),
+                err
+            ),
+        }
+    }
+
+    /// Checks that the mass properties of this body can be handed to nphysics.
+    pub fn validate_mass_properties(&self) -> Result<(), MassPropertiesError> {
+        if !self.mass.is_finite() || self.mass <= 0.0 {
+            return Err(MassPropertiesError::NonPositiveMass(self.mass));
+        }
+
+        let asymmetry = (self.angular_mass - self.angular_mass.transpose()).amax();
+        if !asymmetry.is_finite() || asymmetry > EPSILON * self.angular_mass.amax().max(1.0) {
+            return Err(MassPropertiesError::NonSymmetricAngularMass(
+                self.angular_mass,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Error when the mass properties of a `DynamicBody` can't be applied to the physics world.
+#[derive(Debug)]
+pub enum MassPropertiesError {
+    /// The mass is zero, negative or not a number.
+    NonPositiveMass(f32),
+    /// The angular inertia tensor isn't symmetric.
+    NonSymmetricAngularMass(Matrix3<f32>),
+}
+
+impl std::fmt::Display for MassPropertiesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MassPropertiesError::NonPositiveMass(mass) => {
+                write!(f, "Mass must be strictly positive, got {}!", mass)
+            }
+            MassPropertiesError::NonSymmetricAngularMass(angular_mass) => write!(
+                f,
+                "Angular inertia tensor must be symmetric, got {}!",
+                angular_mass
+            ),
+        }
+    }
 }
 
 impl Component for DynamicBody {
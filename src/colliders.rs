@@ -1,8 +1,11 @@
+use crate::materials::PhysicsMaterials;
+use crate::shapes::{shape_mass_properties, transform_mass_properties, ShapeDesc};
 use amethyst::ecs::{Component, DenseVecStorage, FlaggedStorage};
-use nalgebra::Isometry3;
+use nalgebra::{Isometry3, Matrix3};
 use ncollide::{shape::ShapeHandle, world::GeometricQueryType};
 use ncollide3d::world::CollisionGroups;
 use nphysics::material::BasicMaterial;
+use nphysics::math::Point;
 use nphysics::object::ColliderHandle;
 
 #[derive(Clone, Serialize, Deserialize, Debug, new)]
@@ -33,26 +36,105 @@ impl ColliderType {
     }
 }
 
-#[derive(new, Clone, Builder)]
+#[derive(new, Clone, Builder, Serialize, Deserialize)]
 #[builder(pattern = "owned")]
 pub struct Collider {
     #[new(default)]
-    //#[serde(skip)]
+    #[serde(skip)]
     #[builder(default)]
     pub(crate) handle: Option<ColliderHandle>,
     /// Warning: Changing the margin after inserting the entity will have no effect.
+    #[serde(default = "default_margin")]
     pub margin: f32,
-    pub shape: ShapeHandle<f32>,
+    /// Description of the shape, built into `shape_handle` when inserted or modified.
+    pub shape: ShapeDesc,
+    #[new(default)]
+    #[serde(skip)]
+    #[builder(setter(skip))]
+    pub(crate) shape_handle: Option<ShapeHandle<f32>>,
+    #[serde(default = "Isometry3::identity")]
     pub offset_from_parent: Isometry3<f32>,
+    /// Pose of the owning entity in the local space of the body the collider is attached to.
+    /// Differs from the identity when the body belongs to an ancestor of the entity.
+    #[new(value = "Isometry3::identity()")]
+    #[serde(skip, default = "Isometry3::identity")]
+    #[builder(setter(skip), default = "Isometry3::identity()")]
+    pub(crate) frame_in_body: Isometry3<f32>,
+    #[serde(default)]
     pub physics_material: BasicMaterial<f32>,
+    /// Name of a preset of the `PhysicsMaterials` resource, used instead of `physics_material`.
+    #[new(default)]
+    #[serde(default)]
+    #[builder(default)]
+    pub material_preset: Option<String>,
+    #[serde(default)]
     pub collision_group: CollisionGroups,
+    #[serde(default)]
     pub query_type: ColliderType,
+    /// Density of the shape, used when the mass of the parent `DynamicBody` is computed from its
+    /// colliders.
+    #[new(value = "1.0")]
+    #[builder(default = "1.0")]
+    #[serde(default = "default_density")]
+    pub density: f32,
+}
+
+fn default_margin() -> f32 {
+    0.01
+}
+
+fn default_density() -> f32 {
+    1.0
+}
+
+impl Collider {
+    /// The collision shape built from `shape`, once this collider has been inserted into the
+    /// physics world.
+    pub fn shape_handle(&self) -> Option<&ShapeHandle<f32>> {
+        self.shape_handle.as_ref()
+    }
+
+    /// Material of this collider, taken from its preset if it has one.
+    pub fn material(&self, materials: &PhysicsMaterials) -> BasicMaterial<f32> {
+        match &self.material_preset {
+            Some(name) => match materials.get(name) {
+                Some(material) => *material,
+                None => {
+                    warn!(
+                        "Unknown material preset {:?}, using the material of the collider.",
+                        name
+                    );
+                    self.physics_material
+                }
+            },
+            None => self.physics_material,
+        }
+    }
+
+    /// Mass, center of mass and angular inertia of this collider, expressed in the local space of
+    /// the parent body. The angular inertia is relative to the center of mass.
+    ///
+    /// Returns `None` if the shape hasn't been built yet or has no volume.
+    pub fn mass_properties(&self) -> Option<(f32, Point<f32>, Matrix3<f32>)> {
+        let shape = self.shape_handle.as_ref()?;
+        let part = shape_mass_properties(&**shape, self.density)?;
+        Some(transform_mass_properties(
+            &(self.frame_in_body * self.offset_from_parent),
+            part,
+        ))
+    }
 }
 
 impl From<ShapeHandle<f32>> for ColliderBuilder {
     fn from(shape: ShapeHandle<f32>) -> ColliderBuilder {
+        ColliderBuilder::from(ShapeDesc::Custom(shape))
+    }
+}
+
+impl From<ShapeDesc> for ColliderBuilder {
+    fn from(shape: ShapeDesc) -> ColliderBuilder {
         ColliderBuilder::default()
-            .margin(0.01)
+            .margin(default_margin())
             .shape(shape)
             .offset_from_parent(Isometry3::identity())
             .query_type(ColliderType::default())
@@ -67,8 +149,36 @@ impl ColliderBuilder {
         self.query_type = Some(ColliderType::Trigger);
         self
     }
+
+    /// Uses the material preset with the given name, registered in the `PhysicsMaterials`
+    /// resource.
+    pub fn preset<S: Into<String>>(mut self, name: S) -> Self {
+        self.material_preset = Some(Some(name.into()));
+        self
+    }
 }
 
 impl Component for Collider {
     type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
+
+/// Several colliders attached to the same entity, as an alternative to building a compound shape.
+///
+/// Each of them is a separate collider in the physics world, reporting its own contact and
+/// proximity events for the owning entity. Modifying the component recreates all of them.
+#[derive(new, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Colliders {
+    pub colliders: Vec<Collider>,
+}
+
+impl Colliders {
+    /// Handles of the colliders that have been inserted into the physics world.
+    pub fn handles<'a>(&'a self) -> impl Iterator<Item = ColliderHandle> + 'a {
+        self.colliders.iter().filter_map(|collider| collider.handle)
+    }
+}
+
+impl Component for Colliders {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
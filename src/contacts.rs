@@ -0,0 +1,243 @@
+use amethyst::ecs::world::EntitiesRes;
+use amethyst::ecs::{Component, DenseVecStorage, Entity};
+use nalgebra::DVector;
+use ncollide::query::ContactId;
+use nphysics::detection::ColliderContactManifold;
+use nphysics::material::MaterialsCoefficientsTable;
+use nphysics::object::{BodySet, ColliderHandle};
+use nphysics::solver::{ConstraintSet, ContactModel, IntegrationParameters};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+/// Opt-in component listing the entities currently touching or overlapping its owner.
+///
+/// Filled by the `PhysicsStepperSystem` from contact and proximity events, so it is only up to
+/// date after that system ran. `entered` and `exited` only contain the changes of the last frame.
+/// Entities the owner already touches when the component is inserted are reported as entered on
+/// the next run of that system.
+#[derive(Default, Clone, Debug)]
+pub struct Contacts {
+    /// Number of touching collider pairs per entity.
+    touching: HashMap<Entity, usize>,
+    entered: Vec<Entity>,
+    exited: Vec<Entity>,
+    /// Whether the pairs touching before the component was inserted have been added.
+    seeded: bool,
+}
+
+impl Contacts {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Entities currently touching or overlapping the owner.
+    pub fn touching<'a>(&'a self) -> impl Iterator<Item = Entity> + 'a {
+        self.touching.keys().cloned()
+    }
+
+    pub fn is_touching(&self, entity: Entity) -> bool {
+        self.touching.contains_key(&entity)
+    }
+
+    /// Entities that started touching the owner during the last frame.
+    pub fn entered(&self) -> &[Entity] {
+        &self.entered
+    }
+
+    /// Entities that stopped touching the owner during the last frame.
+    pub fn exited(&self) -> &[Entity] {
+        &self.exited
+    }
+
+    /// Forgets the changes of the previous frame and the entities that have been deleted since.
+    pub(crate) fn begin_frame(&mut self, entities: &EntitiesRes) {
+        self.entered.clear();
+        self.exited.clear();
+
+        let exited = &mut self.exited;
+        self.touching.retain(|entity, _| {
+            let alive = entities.is_alive(*entity);
+            if !alive {
+                exited.push(*entity);
+            }
+            alive
+        });
+    }
+
+    /// Adds the entities the owner was already touching when the component was inserted, out of
+    /// all the touching pairs of entities. Only done once.
+    pub(crate) fn seed(&mut self, owner: Entity, pairs: impl Iterator<Item = (Entity, Entity)>) {
+        if self.seeded {
+            return;
+        }
+        self.seeded = true;
+        for (entity1, entity2) in pairs {
+            if entity1 == owner {
+                self.add(entity2);
+            }
+            if entity2 == owner {
+                self.add(entity1);
+            }
+        }
+    }
+
+    pub(crate) fn add(&mut self, entity: Entity) {
+        let count = self.touching.entry(entity).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            self.entered.push(entity);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        let remaining = match self.touching.get_mut(&entity) {
+            Some(count) => {
+                *count -= 1;
+                *count
+            }
+            None => return,
+        };
+        if remaining == 0 {
+            self.touching.remove(&entity);
+            self.exited.push(entity);
+        }
+    }
+}
+
+impl Component for Contacts {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Impulses the solver applied between pairs of colliders during the last physics step, shared
+/// between the `MeasuredContactModel` writing them and the `PhysicsStepperSystem` reading them.
+#[derive(Clone, Default)]
+pub(crate) struct ContactImpulses(Arc<Mutex<HashMap<(ColliderHandle, ColliderHandle), f32>>>);
+
+impl ContactImpulses {
+    /// Sum of the normal impulses applied at the contact points of both colliders, in any order.
+    pub(crate) fn get(&self, collider1: ColliderHandle, collider2: ColliderHandle) -> f32 {
+        let impulses = match self.0.lock() {
+            Ok(impulses) => impulses,
+            Err(_) => return 0.0,
+        };
+        impulses
+            .get(&(collider1, collider2))
+            .or_else(|| impulses.get(&(collider2, collider1)))
+            .cloned()
+            .unwrap_or(0.0)
+    }
+
+    /// Forgets the impulses of the previous step, for the pairs the solver doesn't handle anymore.
+    pub(crate) fn clear(&self) {
+        self.set(HashMap::new());
+    }
+
+    fn set(&self, impulses: HashMap<(ColliderHandle, ColliderHandle), f32>) {
+        if let Ok(mut current) = self.0.lock() {
+            *current = impulses;
+        }
+    }
+}
+
+/// nphysics contact model, keeping track of the impulses the solver applies at each contact.
+///
+/// Normal impulses are read back from the solver rows of the contacts after each step, and summed
+/// per pair of colliders into the shared `ContactImpulses`.
+pub(crate) struct MeasuredContactModel<M> {
+    model: M,
+    impulses: ContactImpulses,
+    /// Colliders and contacts of the manifolds of the last step.
+    manifolds: Vec<(ColliderHandle, ColliderHandle, Vec<ContactId>)>,
+    /// Non-penetration rows generated by the model during the last step, in each list of solver
+    /// constraints.
+    unilateral_ground: Range<usize>,
+    unilateral: Range<usize>,
+}
+
+impl<M: ContactModel<f32>> MeasuredContactModel<M> {
+    pub(crate) fn new(model: M, impulses: ContactImpulses) -> Self {
+        MeasuredContactModel {
+            model,
+            impulses,
+            manifolds: Vec::new(),
+            unilateral_ground: 0..0,
+            unilateral: 0..0,
+        }
+    }
+}
+
+impl<M: ContactModel<f32>> ContactModel<f32> for MeasuredContactModel<M> {
+    fn num_velocity_constraints(&self, manifold: &ColliderContactManifold<f32>) -> usize {
+        self.model.num_velocity_constraints(manifold)
+    }
+
+    fn constraints(
+        &mut self,
+        parameters: &IntegrationParameters<f32>,
+        material_coefficients: &MaterialsCoefficientsTable<f32>,
+        bodies: &BodySet<f32>,
+        ext_vels: &DVector<f32>,
+        manifolds: &[ColliderContactManifold<f32>],
+        ground_j_id: &mut usize,
+        j_id: &mut usize,
+        jacobians: &mut [f32],
+        constraints: &mut ConstraintSet<f32, ContactId>,
+    ) {
+        let start = (
+            constraints.velocity.unilateral_ground.len(),
+            constraints.velocity.unilateral.len(),
+        );
+        self.model.constraints(
+            parameters,
+            material_coefficients,
+            bodies,
+            ext_vels,
+            manifolds,
+            ground_j_id,
+            j_id,
+            jacobians,
+            constraints,
+        );
+        self.unilateral_ground = start.0..constraints.velocity.unilateral_ground.len();
+        self.unilateral = start.1..constraints.velocity.unilateral.len();
+
+        self.manifolds = manifolds
+            .iter()
+            .map(|manifold| {
+                let contacts = manifold
+                    .manifold
+                    .contacts()
+                    .map(|tracked| tracked.id)
+                    .collect();
+                (
+                    manifold.collider1.handle(),
+                    manifold.collider2.handle(),
+                    contacts,
+                )
+            })
+            .collect();
+    }
+
+    fn cache_impulses(&mut self, constraints: &ConstraintSet<f32, ContactId>) {
+        self.model.cache_impulses(constraints);
+
+        // Rows are matched to the contacts through their warmstarting cache id.
+        let rows = constraints.velocity.unilateral_ground[self.unilateral_ground.clone()]
+            .iter()
+            .map(|row| (row.cache_id, row.impulse))
+            .chain(
+                constraints.velocity.unilateral[self.unilateral.clone()]
+                    .iter()
+                    .map(|row| (row.cache_id, row.impulse)),
+            )
+            .collect::<HashMap<_, _>>();
+
+        let mut impulses = HashMap::new();
+        for (collider1, collider2, contacts) in self.manifolds.drain(..) {
+            let impulse = contacts.iter().filter_map(|id| rows.get(id)).sum::<f32>();
+            *impulses.entry((collider1, collider2)).or_insert(0.0) += impulse;
+        }
+        self.impulses.set(impulses);
+    }
+}
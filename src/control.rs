@@ -0,0 +1,70 @@
+/// Resource controlling how the `PhysicsStepperSystem` advances the simulation, independently of
+/// the rest of the game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicsControl {
+    paused: bool,
+    /// Steps requested through `step`, not yet consumed by the `PhysicsStepperSystem`.
+    pending_steps: u32,
+    time_scale: f32,
+}
+
+impl Default for PhysicsControl {
+    fn default() -> Self {
+        PhysicsControl {
+            paused: false,
+            pending_steps: 0,
+            time_scale: 1.0,
+        }
+    }
+}
+
+impl PhysicsControl {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Stops advancing the simulation with the game time. Bodies keep their state, including the
+    /// forces applied to them, until the simulation is resumed or stepped.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advances the simulation by `steps` timesteps on top of the game time, paused or not. Steps
+    /// beyond the iteration limit of the `PhysicsStepperSystem` are simulated on the next frames.
+    pub fn step(&mut self, steps: u32) {
+        self.pending_steps += steps;
+    }
+
+    pub fn pending_steps(&self) -> u32 {
+        self.pending_steps
+    }
+
+    pub(crate) fn take_pending_steps(&mut self) -> u32 {
+        std::mem::replace(&mut self.pending_steps, 0)
+    }
+
+    /// Multiplier of the game time simulated by the physics, on top of `Time::time_scale`.
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Sets the multiplier of the game time simulated by the physics. Negative values are clamped
+    /// to 0, as the simulation can't go backwards.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        if time_scale < 0.0 {
+            warn!(
+                "Negative physics time scale {} clamped to 0, the simulation can't go backwards.",
+                time_scale
+            );
+        }
+        self.time_scale = time_scale.max(0.0);
+    }
+}
@@ -0,0 +1,83 @@
+use amethyst::ecs::Entity;
+use ncollide::events::ProximityEvent;
+use nphysics::math::{Point, Vector};
+use nphysics::object::ColliderHandle;
+
+/// Whether two colliders started or stopped touching.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContactState {
+    Started,
+    Stopped,
+}
+
+/// A single point of a contact manifold, in world space.
+#[derive(Clone, Copy, Debug)]
+pub struct ContactPoint {
+    /// Contact point on the first collider.
+    pub world1: Point<f32>,
+    /// Contact point on the second collider.
+    pub world2: Point<f32>,
+    /// Contact normal, pointing from the first collider towards the second.
+    pub normal: Vector<f32>,
+    /// Penetration depth. Negative if the colliders are only close to each other.
+    pub depth: f32,
+}
+
+/// Contact between two colliders, written by the `PhysicsStepperSystem` after each step.
+#[derive(Clone, Debug)]
+pub struct EntityContactEvent {
+    pub entity1: Entity,
+    pub entity2: Entity,
+    pub collider1: ColliderHandle,
+    pub collider2: ColliderHandle,
+    pub state: ContactState,
+    /// Contact points at the end of the step. Empty when the contact stopped.
+    pub contacts: Vec<ContactPoint>,
+    /// Sum of the normal impulses the solver applied at the contact points of both colliders
+    /// during the step. Zero when the contact stopped, or if the contact model of the physics world
+    /// was replaced after the `PhysicsStepperSystem` was set up.
+    pub impulse: f32,
+}
+
+impl EntityContactEvent {
+    /// Contact point with the largest penetration depth.
+    pub fn deepest_contact(&self) -> Option<&ContactPoint> {
+        self.contacts
+            .iter()
+            .fold(None, |deepest, contact| match deepest {
+                Some(d) if d.depth >= contact.depth => Some(d),
+                _ => Some(contact),
+            })
+    }
+}
+
+/// A breakable `Joint` broke during a physics step, written by the `PhysicsStepperSystem`.
+#[derive(Clone, Copy, Debug)]
+pub struct JointBrokenEvent {
+    /// Entity owning the `Joint` component.
+    pub joint: Entity,
+    pub entity1: Entity,
+    /// Second entity of the joint, or `None` for the ground.
+    pub entity2: Option<Entity>,
+    /// Magnitude of the linear impulse the solver applied to hold the joint during the step that
+    /// broke it.
+    pub linear_impulse: f32,
+    /// Magnitude of the angular impulse the solver applied to hold the joint during the step that
+    /// broke it.
+    pub angular_impulse: f32,
+}
+
+/// A dynamic body fell asleep, either on its own or on request, written by the
+/// `SyncBodiesFromPhysicsSystem`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodySleepEvent {
+    pub entity: Entity,
+}
+
+/// A sleeping dynamic body woke up, written by the `SyncBodiesFromPhysicsSystem`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyWakeEvent {
+    pub entity: Entity,
+}
+
+pub type EntityProximityEvent = (Entity, Entity, ProximityEvent);
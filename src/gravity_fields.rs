@@ -0,0 +1,172 @@
+use crate::shapes::ShapeDesc;
+use amethyst::ecs::{Component, DenseVecStorage, FlaggedStorage};
+use nalgebra::{Isometry3, Point3, Vector3};
+use ncollide::shape::ShapeHandle;
+use nphysics::algebra::ForceType;
+use nphysics::force_generator::ForceGenerator;
+use nphysics::math::Force;
+use nphysics::object::{Body, BodyHandle, BodySet};
+use nphysics::solver::IntegrationParameters;
+use std::sync::{Arc, Mutex};
+
+/// How a `GravityField` accelerates the bodies inside it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum GravityFieldKind {
+    /// Constant acceleration, in world space.
+    Uniform(Vector3<f32>),
+    /// Constant acceleration along the `-y` axis of the field entity, turning along with it.
+    Directional { strength: f32 },
+    /// Acceleration toward the origin of the field entity, of `strength / distance^falloff`.
+    /// A falloff of 2 gives the gravity of a planet. Distances below `min_distance` are clamped to
+    /// avoid infinite accelerations near the center.
+    Radial {
+        strength: f32,
+        falloff: f32,
+        min_distance: f32,
+    },
+}
+
+impl GravityFieldKind {
+    /// Acceleration of a body at `point`, in a field whose entity is at `position`.
+    pub fn acceleration(&self, position: &Isometry3<f32>, point: &Point3<f32>) -> Vector3<f32> {
+        match *self {
+            GravityFieldKind::Uniform(acceleration) => acceleration,
+            GravityFieldKind::Directional { strength } => {
+                position.rotation * -Vector3::y() * strength
+            }
+            GravityFieldKind::Radial {
+                strength,
+                falloff,
+                min_distance,
+            } => {
+                let to_center = position.translation.vector - point.coords;
+                let distance = to_center.norm();
+                if distance == 0.0 {
+                    return Vector3::zeros();
+                }
+                to_center / distance * strength / distance.max(min_distance).powf(falloff)
+            }
+        }
+    }
+}
+
+/// Component accelerating the dynamic bodies whose center of mass is inside its volume.
+///
+/// Of all the fields containing a body, only those with the highest priority apply, their
+/// accelerations being summed. If one of them overrides gravity, the gravity of the body itself is
+/// cancelled, which allows zero-g zones.
+#[derive(Serialize, Deserialize, Clone, new)]
+pub struct GravityField {
+    pub kind: GravityFieldKind,
+    /// Volume of the field, relative to its entity. The field is unbounded if `None`.
+    #[new(default)]
+    #[serde(default)]
+    pub shape: Option<ShapeDesc>,
+    #[new(default)]
+    #[serde(default)]
+    pub priority: i32,
+    #[new(default)]
+    #[serde(default)]
+    pub overrides_gravity: bool,
+}
+
+impl Component for GravityField {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+/// State of a `GravityField` for the current frame.
+pub(crate) struct FieldState {
+    pub kind: GravityFieldKind,
+    pub shape: Option<ShapeHandle<f32>>,
+    pub position: Isometry3<f32>,
+    pub priority: i32,
+    pub overrides_gravity: bool,
+}
+
+impl FieldState {
+    fn contains(&self, point: &Point3<f32>) -> bool {
+        match &self.shape {
+            Some(shape) => shape
+                .as_point_query()
+                .map_or(false, |query| query.contains_point(&self.position, point)),
+            None => true,
+        }
+    }
+}
+
+/// A body the gravity fields may apply to, with the gravity it receives outside of them.
+pub(crate) struct FieldBody {
+    pub handle: BodyHandle,
+    pub gravity: Vector3<f32>,
+}
+
+/// Fields and bodies shared between the `SyncGravityFieldsToPhysicsSystem`, which updates them
+/// every frame, and the force generator applying the fields on every physics step.
+#[derive(Default)]
+pub(crate) struct GravityFieldsState {
+    pub fields: Vec<FieldState>,
+    pub bodies: Vec<FieldBody>,
+}
+
+impl GravityFieldsState {
+    /// Acceleration of a body at `point` subject to `gravity`, if any field contains it.
+    fn acceleration(&self, point: &Point3<f32>, gravity: &Vector3<f32>) -> Option<Vector3<f32>> {
+        let fields = self
+            .fields
+            .iter()
+            .filter(|field| field.contains(point))
+            .collect::<Vec<_>>();
+        let priority = fields.iter().map(|field| field.priority).max()?;
+
+        let mut acceleration = Vector3::zeros();
+        let mut overrides_gravity = false;
+        for field in fields.iter().filter(|field| field.priority == priority) {
+            acceleration += field.kind.acceleration(&field.position, point);
+            overrides_gravity |= field.overrides_gravity;
+        }
+        if overrides_gravity {
+            acceleration -= gravity;
+        }
+        Some(acceleration)
+    }
+}
+
+/// nphysics force generator applying the gravity fields.
+pub(crate) struct GravityFieldsForceGenerator {
+    state: Arc<Mutex<GravityFieldsState>>,
+}
+
+impl GravityFieldsForceGenerator {
+    pub fn new(state: Arc<Mutex<GravityFieldsState>>) -> Self {
+        GravityFieldsForceGenerator { state }
+    }
+}
+
+impl ForceGenerator<f32> for GravityFieldsForceGenerator {
+    fn apply(&mut self, _: &IntegrationParameters<f32>, bodies: &mut BodySet<f32>) -> bool {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => {
+                error!("Gravity fields state poisoned, removing the force generator.");
+                return false;
+            }
+        };
+
+        for body in &state.bodies {
+            let rigid_body = match bodies.rigid_body_mut(body.handle) {
+                Some(rigid_body) if rigid_body.is_dynamic() => rigid_body,
+                _ => continue,
+            };
+            let point = rigid_body.center_of_mass();
+            if let Some(acceleration) = state.acceleration(&point, &body.gravity) {
+                rigid_body.apply_force(
+                    0,
+                    &Force::linear(acceleration),
+                    ForceType::AccelerationChange,
+                    false,
+                );
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,134 @@
+use amethyst::ecs::world::Index;
+use amethyst::ecs::Entity;
+use nphysics::object::{BodyHandle, ColliderHandle};
+use nphysics::world::ConstraintHandle;
+use std::collections::HashMap;
+
+/// Maps the handles of the physics world to the entities owning them, and back.
+///
+/// Kept up to date by the synchronization systems when bodies, colliders and joints are inserted
+/// into or removed from the physics world. Entities are keyed by index, so that the handles of deleted
+/// entities can still be found. Lookups by entity check its generation, so that a deleted entity
+/// never gets the handles of a new entity reusing its index.
+#[derive(Default)]
+pub struct PhysicsHandles {
+    body_entities: HashMap<BodyHandle, Entity>,
+    collider_entities: HashMap<ColliderHandle, Entity>,
+    entity_bodies: HashMap<Index, BodyHandle>,
+    entity_colliders: HashMap<Index, Vec<ColliderHandle>>,
+    joint_entities: HashMap<ConstraintHandle, Entity>,
+    entity_joints: HashMap<Index, ConstraintHandle>,
+}
+
+impl PhysicsHandles {
+    /// Entity owning the given body.
+    pub fn body_entity(&self, handle: BodyHandle) -> Option<Entity> {
+        self.body_entities.get(&handle).cloned()
+    }
+
+    /// Entity owning the given collider.
+    pub fn collider_entity(&self, handle: ColliderHandle) -> Option<Entity> {
+        self.collider_entities.get(&handle).cloned()
+    }
+
+    /// Body of the given entity, if it has been inserted into the physics world.
+    pub fn body(&self, entity: Entity) -> Option<BodyHandle> {
+        self.entity_bodies
+            .get(&entity.id())
+            .filter(|handle| self.body_entities.get(*handle) == Some(&entity))
+            .cloned()
+    }
+
+    /// Colliders of the given entity that have been inserted into the physics world.
+    pub fn colliders(&self, entity: Entity) -> &[ColliderHandle] {
+        self.entity_colliders
+            .get(&entity.id())
+            .filter(|handles| {
+                handles
+                    .first()
+                    .and_then(|handle| self.collider_entities.get(handle))
+                    == Some(&entity)
+            })
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Entity owning the `Joint` of the given constraint.
+    pub fn joint_entity(&self, handle: ConstraintHandle) -> Option<Entity> {
+        self.joint_entities.get(&handle).cloned()
+    }
+
+    /// Constraint of the `Joint` of the given entity, if it has been inserted into the physics
+    /// world.
+    pub fn joint(&self, entity: Entity) -> Option<ConstraintHandle> {
+        self.entity_joints
+            .get(&entity.id())
+            .filter(|handle| self.joint_entities.get(*handle) == Some(&entity))
+            .cloned()
+    }
+
+    pub(crate) fn insert_body(&mut self, entity: Entity, handle: BodyHandle) {
+        if let Some(previous) = self.entity_bodies.insert(entity.id(), handle) {
+            self.body_entities.remove(&previous);
+        }
+        self.body_entities.insert(handle, entity);
+    }
+
+    /// Forgets the body of the entity with the given index, returning its handle.
+    pub(crate) fn remove_body(&mut self, id: Index) -> Option<BodyHandle> {
+        let handle = self.entity_bodies.remove(&id)?;
+        self.body_entities.remove(&handle);
+        Some(handle)
+    }
+
+    pub(crate) fn insert_collider(&mut self, entity: Entity, handle: ColliderHandle) {
+        let collider_entities = &self.collider_entities;
+        let handles = self
+            .entity_colliders
+            .entry(entity.id())
+            .or_insert_with(Vec::new);
+        // Colliders of a deleted entity with the same index stay mapped to it, until removed.
+        handles.retain(|h| collider_entities.get(h) == Some(&entity));
+        handles.push(handle);
+        self.collider_entities.insert(handle, entity);
+    }
+
+    /// Forgets a single collider, returning the entity owning it.
+    pub(crate) fn remove_collider(&mut self, handle: ColliderHandle) -> Option<Entity> {
+        let entity = self.collider_entities.remove(&handle)?;
+        if let Some(handles) = self.entity_colliders.get_mut(&entity.id()) {
+            handles.retain(|h| *h != handle);
+            if handles.is_empty() {
+                self.entity_colliders.remove(&entity.id());
+            }
+        }
+        Some(entity)
+    }
+
+    /// Forgets the colliders for which `keep` returns `false`.
+    pub(crate) fn retain_colliders(&mut self, mut keep: impl FnMut(ColliderHandle) -> bool) {
+        let removed = self
+            .collider_entities
+            .keys()
+            .cloned()
+            .filter(|handle| !keep(*handle))
+            .collect::<Vec<_>>();
+        for handle in removed {
+            self.remove_collider(handle);
+        }
+    }
+
+    pub(crate) fn insert_joint(&mut self, entity: Entity, handle: ConstraintHandle) {
+        if let Some(previous) = self.entity_joints.insert(entity.id(), handle) {
+            self.joint_entities.remove(&previous);
+        }
+        self.joint_entities.insert(handle, entity);
+    }
+
+    /// Forgets the joint of the entity with the given index, returning its handle.
+    pub(crate) fn remove_joint(&mut self, id: Index) -> Option<ConstraintHandle> {
+        let handle = self.entity_joints.remove(&id)?;
+        self.joint_entities.remove(&handle);
+        Some(handle)
+    }
+}
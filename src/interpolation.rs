@@ -0,0 +1,104 @@
+use nalgebra::{Isometry3, Translation3, UnitQuaternion};
+use nphysics::math::Velocity;
+use nphysics::object::BodyHandle;
+use std::collections::HashMap;
+use std::f32::EPSILON;
+
+/// How the poses of the physics world are written to the transforms of the entities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// The pose after the last physics step is written as is.
+    Disabled,
+    /// The pose is blended between the two last physics steps using the time left in the physics
+    /// accumulator. Transforms lag up to one timestep behind the simulation.
+    Interpolate,
+    /// The pose after the last physics step is moved further along the body velocity by the time
+    /// left in the physics accumulator. Can overshoot when bodies collide.
+    Extrapolate,
+}
+
+impl Default for InterpolationMode {
+    fn default() -> Self {
+        InterpolationMode::Disabled
+    }
+}
+
+/// Resource holding the interpolation mode, and the data the `PhysicsStepperSystem` records for
+/// the `SyncBodiesFromPhysicsSystem` to interpolate transforms.
+#[derive(Default)]
+pub struct PhysicsInterpolation {
+    pub mode: InterpolationMode,
+    /// Simulated time not consumed by the physics steps of the last frame.
+    leftover_time: f32,
+    timestep: f32,
+    /// Poses of the bodies before the last physics step.
+    previous_positions: HashMap<BodyHandle, Isometry3<f32>>,
+}
+
+impl PhysicsInterpolation {
+    pub fn new(mode: InterpolationMode) -> Self {
+        PhysicsInterpolation {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Fraction of a timestep left in the physics accumulator after the last frame.
+    pub fn alpha(&self) -> f32 {
+        if self.timestep > 0.0 {
+            (self.leftover_time / self.timestep).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn set_leftover_time(&mut self, leftover_time: f32, timestep: f32) {
+        self.leftover_time = leftover_time;
+        self.timestep = timestep;
+    }
+
+    pub(crate) fn clear_previous_positions(&mut self) {
+        self.previous_positions.clear();
+    }
+
+    pub(crate) fn record_previous_position(
+        &mut self,
+        handle: BodyHandle,
+        position: Isometry3<f32>,
+    ) {
+        self.previous_positions.insert(handle, position);
+    }
+
+    /// Pose to display for the body, given its current pose and velocity.
+    pub fn position(
+        &self,
+        handle: BodyHandle,
+        current: &Isometry3<f32>,
+        velocity: &Velocity<f32>,
+    ) -> Isometry3<f32> {
+        match self.mode {
+            InterpolationMode::Disabled => *current,
+            InterpolationMode::Interpolate => match self.previous_positions.get(&handle) {
+                Some(previous) => interpolate(previous, current, self.alpha()),
+                None => *current,
+            },
+            InterpolationMode::Extrapolate => {
+                let time = self.alpha() * self.timestep;
+                Isometry3::from_parts(
+                    Translation3::from(current.translation.vector + velocity.linear * time),
+                    UnitQuaternion::new(velocity.angular * time) * current.rotation,
+                )
+            }
+        }
+    }
+}
+
+fn interpolate(from: &Isometry3<f32>, to: &Isometry3<f32>, t: f32) -> Isometry3<f32> {
+    Isometry3::from_parts(
+        Translation3::from(from.translation.vector.lerp(&to.translation.vector, t)),
+        // Rotations half a turn apart have no unique interpolation, snap to the latest one.
+        from.rotation
+            .try_slerp(&to.rotation, t, EPSILON)
+            .unwrap_or(to.rotation),
+    )
+}
@@ -0,0 +1,566 @@
+use crate::handles::PhysicsHandles;
+use crate::PhysicsWorld;
+use amethyst::ecs::{Component, DenseVecStorage, Entity, FlaggedStorage, WriteStorage};
+use nalgebra::{DVector, Isometry3, Point3, Vector3};
+use nphysics::algebra::ForceType;
+use nphysics::joint::{
+    BallConstraint, CylindricalConstraint, FixedConstraint, JointConstraint, PrismaticConstraint,
+    RevoluteConstraint, UniversalConstraint,
+};
+use nphysics::math::{Force, Velocity};
+use nphysics::object::{Body, BodyHandle, BodyPartHandle, BodySet};
+use nphysics::solver::{
+    GenericNonlinearConstraint, IntegrationParameters, LinearConstraints,
+    NonlinearConstraintGenerator,
+};
+use nphysics::world::ConstraintHandle;
+use std::f32::consts::FRAC_PI_2;
+use std::ops::Range;
+
+/// Kind of a `Joint`, telling which relative motions of both bodies are allowed.
+///
+/// Axes are taken from the anchor frames of the joint: the main axis is the `x` axis of each
+/// frame, the second axis of a universal joint is the `y` axis of the second frame.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JointType {
+    /// No relative motion.
+    Fixed,
+    /// Free rotation around the anchor point.
+    Ball,
+    /// Rotation around the joint axis.
+    Revolute,
+    /// Translation along the joint axis.
+    Prismatic,
+    /// Rotation around and translation along the joint axis.
+    Cylindrical,
+    /// Rotation around two perpendicular axes.
+    Universal,
+}
+
+impl JointType {
+    /// Number of solver rows restricting the relative translation of the bodies. nphysics joint
+    /// constraints generate them before the rows restricting the relative rotation.
+    fn linear_rows(self) -> usize {
+        match self {
+            JointType::Fixed | JointType::Ball | JointType::Revolute | JointType::Universal => 3,
+            JointType::Prismatic | JointType::Cylindrical => 2,
+        }
+    }
+
+    /// Whether the limits of the joint restrict a translation rather than a rotation.
+    fn has_linear_limits(self) -> bool {
+        self == JointType::Prismatic
+    }
+}
+
+/// What a `JointMotor` tries to reach.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MotorTarget {
+    /// Angular velocity of a revolute joint or linear velocity of a prismatic joint.
+    Velocity(f32),
+    /// Angle of a revolute joint or offset of a prismatic joint, reached at up to `max_velocity`.
+    Position { position: f32, max_velocity: f32 },
+}
+
+/// Motor driving the second body of a revolute or prismatic joint relative to the first one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct JointMotor {
+    pub target: MotorTarget,
+    /// Maximum torque of a revolute joint motor, or force of a prismatic joint motor.
+    pub max_force: f32,
+}
+
+/// Limits of the angle of a revolute joint, or of the offset of a prismatic joint.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct JointLimits {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+/// State of a revolute or prismatic joint, read back from the physics world.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct JointState {
+    /// Angle of a revolute joint, or offset of a prismatic joint.
+    pub position: f32,
+    /// Angular velocity of a revolute joint, or linear velocity of a prismatic joint.
+    pub velocity: f32,
+    /// Impulse applied by the motor during the last frame.
+    pub motor_impulse: f32,
+}
+
+/// Joint connecting the bodies of two entities, or one entity and the ground.
+///
+/// The joint can be attached to any entity, including one of the two connected ones. Both
+/// connected entities need a `DynamicBody`; the joint is created in the physics world once both
+/// bodies are, and removed when either of them is.
+///
+/// The motor and limits can be changed at any time. Changing the type, bodies or anchors of the
+/// joint recreates it in the physics world.
+///
+/// A joint with a maximum impulse breaks when the solver has to apply a larger impulse to hold it
+/// during a physics step. Contacts, forces and motors acting on its bodies only count as far as
+/// the joint resists them. It is then removed from the physics world until `broken` is reset.
+#[derive(Clone, Debug, new)]
+pub struct Joint {
+    pub joint_type: JointType,
+    pub body1: Entity,
+    /// Second body of the joint, or the ground if `None`.
+    pub body2: Option<Entity>,
+    /// Frame of the joint in the local space of the first body.
+    pub anchor1: Isometry3<f32>,
+    /// Frame of the joint in the local space of the second body, or in world space for the ground.
+    pub anchor2: Isometry3<f32>,
+    /// Motor of a revolute or prismatic joint.
+    #[new(default)]
+    pub motor: Option<JointMotor>,
+    /// Limits of a revolute or prismatic joint.
+    #[new(default)]
+    pub limits: JointLimits,
+    /// Synchronized from the physics world, writing to it has no effect.
+    #[new(default)]
+    pub state: JointState,
+    /// Linear impulse above which the joint breaks.
+    #[new(default)]
+    pub max_linear_impulse: Option<f32>,
+    /// Angular impulse above which the joint breaks.
+    #[new(default)]
+    pub max_angular_impulse: Option<f32>,
+    /// Set by the `PhysicsStepperSystem` when the joint breaks.
+    #[new(default)]
+    pub broken: bool,
+}
+
+impl Joint {
+    /// Updates the state of the joint of `entity`, only writing it if it changed, so that joints
+    /// are only flagged as modified when their state actually changes.
+    pub(crate) fn set_state_if_changed(
+        joints: &mut WriteStorage<Joint>,
+        entity: Entity,
+        update: impl FnOnce(&mut JointState),
+    ) {
+        let current = match joints.get(entity) {
+            Some(joint) => joint.state,
+            None => return,
+        };
+        let mut state = current;
+        update(&mut state);
+        if state != current {
+            if let Some(joint) = joints.get_mut(entity) {
+                joint.state = state;
+            }
+        }
+    }
+
+    /// Adds the joint constraint between both body parts to the physics world.
+    pub(crate) fn add_to_world(
+        &self,
+        world: &mut PhysicsWorld,
+        part1: BodyPartHandle,
+        part2: BodyPartHandle,
+    ) -> ConstraintHandle {
+        let point1 = Point3::from(self.anchor1.translation.vector);
+        let point2 = Point3::from(self.anchor2.translation.vector);
+        let axis1 = self.anchor1.rotation * Vector3::x_axis();
+        let axis2 = self.anchor2.rotation * Vector3::x_axis();
+
+        match self.joint_type {
+            JointType::Fixed => self.add_measured(
+                world,
+                FixedConstraint::new(
+                    part1,
+                    part2,
+                    point1,
+                    self.anchor1.rotation,
+                    point2,
+                    self.anchor2.rotation,
+                ),
+            ),
+            JointType::Ball => {
+                self.add_measured(world, BallConstraint::new(part1, part2, point1, point2))
+            }
+            JointType::Revolute => self.add_measured(
+                world,
+                RevoluteConstraint::new(part1, part2, point1, axis1, point2, axis2),
+            ),
+            JointType::Prismatic => self.add_measured(
+                world,
+                PrismaticConstraint::new(part1, part2, point1, axis1, point2),
+            ),
+            JointType::Cylindrical => self.add_measured(
+                world,
+                CylindricalConstraint::new(part1, part2, point1, axis1, point2, axis2),
+            ),
+            JointType::Universal => self.add_measured(
+                world,
+                UniversalConstraint::new(
+                    part1,
+                    part2,
+                    point1,
+                    axis1,
+                    point2,
+                    self.anchor2.rotation * Vector3::y_axis(),
+                    FRAC_PI_2,
+                ),
+            ),
+        }
+    }
+
+    fn add_measured<C: JointConstraint<f32>>(
+        &self,
+        world: &mut PhysicsWorld,
+        constraint: C,
+    ) -> ConstraintHandle {
+        world.add_constraint(MeasuredConstraint::new(constraint, self.joint_type))
+    }
+
+    pub fn is_breakable(&self) -> bool {
+        self.max_linear_impulse.is_some() || self.max_angular_impulse.is_some()
+    }
+
+    /// Whether the given impulses are large enough to break the joint.
+    pub fn breaks_under(&self, linear_impulse: f32, angular_impulse: f32) -> bool {
+        self.max_linear_impulse
+            .map_or(false, |max| linear_impulse > max)
+            || self
+                .max_angular_impulse
+                .map_or(false, |max| angular_impulse > max)
+    }
+
+    /// Handles of both connected bodies, if they have been inserted into the physics world.
+    pub(crate) fn body_handles(
+        &self,
+        handles: &PhysicsHandles,
+    ) -> Option<(BodyHandle, BodyHandle)> {
+        let handle1 = handles.body(self.body1)?;
+        let handle2 = match self.body2 {
+            Some(body2) => handles.body(body2)?,
+            None => BodyHandle::ground(),
+        };
+        Some((handle1, handle2))
+    }
+
+    /// Pushes the limits of the joint to its constraint in the physics world.
+    pub(crate) fn apply_limits(&self, world: &mut PhysicsWorld, handle: ConstraintHandle) {
+        match self.joint_type {
+            JointType::Revolute => {
+                if let Some(revolute) = measured_mut::<RevoluteConstraint<f32>>(world, handle) {
+                    match self.limits.min {
+                        Some(min) => revolute.enable_min_angle(min),
+                        None => revolute.disable_min_angle(),
+                    }
+                    match self.limits.max {
+                        Some(max) => revolute.enable_max_angle(max),
+                        None => revolute.disable_max_angle(),
+                    }
+                }
+            }
+            JointType::Prismatic => {
+                if let Some(prismatic) = measured_mut::<PrismaticConstraint<f32>>(world, handle) {
+                    match self.limits.min {
+                        Some(min) => prismatic.enable_min_offset(min),
+                        None => prismatic.disable_min_offset(),
+                    }
+                    match self.limits.max {
+                        Some(max) => prismatic.enable_max_offset(max),
+                        None => prismatic.disable_max_offset(),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Magnitudes of the linear and angular impulses the solver applied to hold the joint during
+    /// the last physics step.
+    pub(crate) fn impulses(&self, world: &PhysicsWorld, handle: ConstraintHandle) -> (f32, f32) {
+        let constraint = world.constraint(handle);
+        let impulses = match self.joint_type {
+            JointType::Fixed => measured_impulses::<FixedConstraint<f32>>(constraint),
+            JointType::Ball => measured_impulses::<BallConstraint<f32>>(constraint),
+            JointType::Revolute => measured_impulses::<RevoluteConstraint<f32>>(constraint),
+            JointType::Prismatic => measured_impulses::<PrismaticConstraint<f32>>(constraint),
+            JointType::Cylindrical => measured_impulses::<CylindricalConstraint<f32>>(constraint),
+            JointType::Universal => measured_impulses::<UniversalConstraint<f32>>(constraint),
+        };
+        impulses.unwrap_or_else(|| {
+            error!("Joint constraint {:?} isn't a measured constraint!", handle);
+            (0.0, 0.0)
+        })
+    }
+
+    /// Position and velocity along the degree of freedom of a revolute or prismatic joint.
+    pub(crate) fn measure(
+        &self,
+        world: &PhysicsWorld,
+        handle1: BodyHandle,
+        handle2: BodyHandle,
+    ) -> Option<(f32, f32)> {
+        let frame1 = body_position(world, handle1)? * self.anchor1;
+        let frame2 = body_position(world, handle2)? * self.anchor2;
+        let axis = frame1.rotation * Vector3::x_axis();
+        let velocity1 = body_velocity(world, handle1);
+        let velocity2 = body_velocity(world, handle2);
+
+        match self.joint_type {
+            JointType::Revolute => {
+                let relative_rotation = frame1.rotation.inverse() * frame2.rotation;
+                Some((
+                    relative_rotation.scaled_axis().x,
+                    (velocity2.angular - velocity1.angular).dot(&axis),
+                ))
+            }
+            JointType::Prismatic => Some((
+                (frame2.translation.vector - frame1.translation.vector).dot(&axis),
+                (velocity2.linear - velocity1.linear).dot(&axis),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Applies the motor force for a single physics step, returning the applied impulse.
+    pub(crate) fn apply_motor(
+        &self,
+        world: &mut PhysicsWorld,
+        handle1: BodyHandle,
+        handle2: BodyHandle,
+        timestep: f32,
+    ) -> f32 {
+        let motor = match self.motor {
+            Some(motor) => motor,
+            None => return 0.0,
+        };
+        let (position, velocity) = match self.measure(world, handle1, handle2) {
+            Some(state) => state,
+            None => return 0.0,
+        };
+        let axis = match body_position(world, handle1) {
+            Some(position) => (position * self.anchor1).rotation * Vector3::x_axis(),
+            None => return 0.0,
+        };
+        let angular = self.joint_type == JointType::Revolute;
+
+        let target_velocity = match motor.target {
+            MotorTarget::Velocity(target) => target,
+            MotorTarget::Position {
+                position: target,
+                max_velocity,
+            } => ((target - position) / timestep)
+                .max(-max_velocity)
+                .min(max_velocity),
+        };
+
+        // Inertia of both bodies along the joint axis, combined as if they were in series.
+        let inverse_inertia = |handle: BodyHandle| {
+            let body = match world.rigid_body(handle) {
+                Some(body) if body.is_dynamic() => body,
+                _ => return 0.0,
+            };
+            let inertia = body.inertia();
+            let inertia = if angular {
+                axis.dot(&(inertia.angular * axis.into_inner()))
+            } else {
+                inertia.linear
+            };
+            if inertia > 0.0 {
+                1.0 / inertia
+            } else {
+                0.0
+            }
+        };
+        let inverse_inertia = inverse_inertia(handle1) + inverse_inertia(handle2);
+        if inverse_inertia <= 0.0 {
+            return 0.0;
+        }
+
+        let force = ((target_velocity - velocity) / (inverse_inertia * timestep))
+            .max(-motor.max_force)
+            .min(motor.max_force);
+        let force_vector = axis.into_inner() * force;
+        let force_vector = if angular {
+            Force::torque(force_vector)
+        } else {
+            Force::linear(force_vector)
+        };
+
+        if let Some(body) = world.rigid_body_mut(handle2) {
+            body.apply_force(0, &force_vector, ForceType::Force, true);
+        }
+        if let Some(body) = world.rigid_body_mut(handle1) {
+            body.apply_force(0, &-force_vector, ForceType::Force, true);
+        }
+
+        force.abs() * timestep
+    }
+}
+
+/// nphysics joint constraint, keeping track of the impulses the solver applies to hold it.
+///
+/// The solver impulses of every row of the constraint are read back after each step. Rows restrict
+/// the relative velocity of the bodies along or around orthogonal unit axes, so the impulses of
+/// the linear rows and of the angular rows are summed as vectors.
+pub(crate) struct MeasuredConstraint<C> {
+    constraint: C,
+    joint_type: JointType,
+    /// Rows generated by the constraint during the last step, in each list of solver constraints.
+    bilateral_ground: Range<usize>,
+    bilateral: Range<usize>,
+    unilateral_ground: Range<usize>,
+    unilateral: Range<usize>,
+    linear_impulse: f32,
+    angular_impulse: f32,
+}
+
+impl<C: JointConstraint<f32>> MeasuredConstraint<C> {
+    fn new(constraint: C, joint_type: JointType) -> Self {
+        MeasuredConstraint {
+            constraint,
+            joint_type,
+            bilateral_ground: 0..0,
+            bilateral: 0..0,
+            unilateral_ground: 0..0,
+            unilateral: 0..0,
+            linear_impulse: 0.0,
+            angular_impulse: 0.0,
+        }
+    }
+}
+
+impl<C: JointConstraint<f32>> JointConstraint<f32> for MeasuredConstraint<C> {
+    fn is_active(&self, bodies: &BodySet<f32>) -> bool {
+        self.constraint.is_active(bodies)
+    }
+
+    fn num_velocity_constraints(&self) -> usize {
+        self.constraint.num_velocity_constraints()
+    }
+
+    fn anchors(&self) -> (BodyPartHandle, BodyPartHandle) {
+        self.constraint.anchors()
+    }
+
+    fn velocity_constraints(
+        &mut self,
+        params: &IntegrationParameters<f32>,
+        bodies: &BodySet<f32>,
+        ext_vels: &DVector<f32>,
+        ground_j_id: &mut usize,
+        j_id: &mut usize,
+        jacobians: &mut [f32],
+        constraints: &mut LinearConstraints<f32, usize>,
+    ) {
+        let start = (
+            constraints.bilateral_ground.len(),
+            constraints.bilateral.len(),
+            constraints.unilateral_ground.len(),
+            constraints.unilateral.len(),
+        );
+        self.constraint.velocity_constraints(
+            params,
+            bodies,
+            ext_vels,
+            ground_j_id,
+            j_id,
+            jacobians,
+            constraints,
+        );
+        self.bilateral_ground = start.0..constraints.bilateral_ground.len();
+        self.bilateral = start.1..constraints.bilateral.len();
+        self.unilateral_ground = start.2..constraints.unilateral_ground.len();
+        self.unilateral = start.3..constraints.unilateral.len();
+    }
+
+    fn cache_impulses(&mut self, constraints: &LinearConstraints<f32, usize>) {
+        self.constraint.cache_impulses(constraints);
+
+        // All rows of a joint are either ground rows or not, depending on its bodies.
+        let rows = constraints.bilateral_ground[self.bilateral_ground.clone()]
+            .iter()
+            .map(|row| row.impulse)
+            .chain(
+                constraints.bilateral[self.bilateral.clone()]
+                    .iter()
+                    .map(|row| row.impulse),
+            );
+        let limits = constraints.unilateral_ground[self.unilateral_ground.clone()]
+            .iter()
+            .map(|row| row.impulse)
+            .chain(
+                constraints.unilateral[self.unilateral.clone()]
+                    .iter()
+                    .map(|row| row.impulse),
+            );
+
+        let linear_rows = self.joint_type.linear_rows();
+        let mut linear = 0.0;
+        let mut angular = 0.0;
+        for (i, impulse) in rows.enumerate() {
+            if i < linear_rows {
+                linear += impulse * impulse;
+            } else {
+                angular += impulse * impulse;
+            }
+        }
+        for impulse in limits {
+            if self.joint_type.has_linear_limits() {
+                linear += impulse * impulse;
+            } else {
+                angular += impulse * impulse;
+            }
+        }
+        self.linear_impulse = linear.sqrt();
+        self.angular_impulse = angular.sqrt();
+    }
+}
+
+impl<C: JointConstraint<f32>> NonlinearConstraintGenerator<f32> for MeasuredConstraint<C> {
+    fn num_position_constraints(&self, bodies: &BodySet<f32>) -> usize {
+        self.constraint.num_position_constraints(bodies)
+    }
+
+    fn position_constraint(
+        &self,
+        i: usize,
+        bodies: &mut BodySet<f32>,
+        jacobians: &mut [f32],
+    ) -> Option<GenericNonlinearConstraint<f32>> {
+        self.constraint.position_constraint(i, bodies, jacobians)
+    }
+}
+
+/// Constraint of the given type in the physics world, unwrapped from its `MeasuredConstraint`.
+fn measured_mut<C: JointConstraint<f32>>(
+    world: &mut PhysicsWorld,
+    handle: ConstraintHandle,
+) -> Option<&mut C> {
+    world
+        .constraint_mut(handle)
+        .downcast_mut::<MeasuredConstraint<C>>()
+        .map(|measured| &mut measured.constraint)
+}
+
+fn measured_impulses<C: JointConstraint<f32>>(
+    constraint: &JointConstraint<f32>,
+) -> Option<(f32, f32)> {
+    constraint
+        .downcast_ref::<MeasuredConstraint<C>>()
+        .map(|measured| (measured.linear_impulse, measured.angular_impulse))
+}
+
+fn body_position(world: &PhysicsWorld, handle: BodyHandle) -> Option<Isometry3<f32>> {
+    if handle.is_ground() {
+        Some(Isometry3::identity())
+    } else {
+        world.rigid_body(handle).map(|body| *body.position())
+    }
+}
+
+fn body_velocity(world: &PhysicsWorld, handle: BodyHandle) -> Velocity<f32> {
+    world
+        .rigid_body(handle)
+        .map(|body| *body.velocity())
+        .unwrap_or_else(Velocity::zero)
+}
+
+impl Component for Joint {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
@@ -15,11 +15,33 @@ extern crate log;
 
 pub mod bodies;
 pub mod colliders;
+pub mod contacts;
+pub mod control;
+pub mod events;
+pub mod gravity_fields;
+pub mod handles;
+pub mod interpolation;
+pub mod joints;
+pub mod materials;
+pub mod prefab;
+pub mod query;
+pub mod shapes;
 pub mod systems;
 pub mod time_step;
 
 pub use self::bodies::*;
 pub use self::colliders::*;
+pub use self::contacts::*;
+pub use self::control::*;
+pub use self::events::*;
+pub use self::gravity_fields::*;
+pub use self::handles::*;
+pub use self::interpolation::*;
+pub use self::joints::*;
+pub use self::materials::*;
+pub use self::prefab::*;
+pub use self::query::*;
+pub use self::shapes::*;
 pub use self::systems::*;
 pub use self::time_step::*;
 
@@ -0,0 +1,75 @@
+use nphysics::material::{BasicMaterial, MaterialCombineMode};
+use std::collections::HashMap;
+
+/// Resource holding named materials, referenced by colliders through `Collider::material_preset`.
+///
+/// Comes with the `"ice"`, `"rubber"` and `"metal"` presets. Changing a preset only affects the
+/// colliders inserted or modified afterwards.
+pub struct PhysicsMaterials {
+    presets: HashMap<String, BasicMaterial<f32>>,
+}
+
+impl Default for PhysicsMaterials {
+    fn default() -> Self {
+        let mut materials = PhysicsMaterials::empty();
+        materials.insert(
+            "ice",
+            material(
+                0.05,
+                0.02,
+                MaterialCombineMode::Average,
+                MaterialCombineMode::Min,
+            ),
+        );
+        materials.insert(
+            "rubber",
+            material(0.8, 0.9, MaterialCombineMode::Max, MaterialCombineMode::Max),
+        );
+        materials.insert(
+            "metal",
+            material(
+                0.3,
+                0.4,
+                MaterialCombineMode::Average,
+                MaterialCombineMode::Average,
+            ),
+        );
+        materials
+    }
+}
+
+impl PhysicsMaterials {
+    /// Creates a resource without any preset.
+    pub fn empty() -> Self {
+        PhysicsMaterials {
+            presets: HashMap::new(),
+        }
+    }
+
+    /// Registers a preset, replacing the previous one with the same name.
+    pub fn insert<S: Into<String>>(&mut self, name: S, material: BasicMaterial<f32>) {
+        self.presets.insert(name.into(), material);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BasicMaterial<f32>> {
+        self.presets.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<BasicMaterial<f32>> {
+        self.presets.remove(name)
+    }
+}
+
+/// Creates a material, choosing how its restitution and friction are combined with the ones of the
+/// other material of a contact.
+pub fn material(
+    restitution: f32,
+    friction: f32,
+    restitution_combine_mode: MaterialCombineMode,
+    friction_combine_mode: MaterialCombineMode,
+) -> BasicMaterial<f32> {
+    let mut material = BasicMaterial::new(restitution, friction);
+    material.restitution_combine_mode = restitution_combine_mode;
+    material.friction_combine_mode = friction_combine_mode;
+    material
+}
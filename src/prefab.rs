@@ -0,0 +1,94 @@
+use crate::bodies::DynamicBody;
+use crate::colliders::{Collider, Colliders};
+use amethyst::assets::PrefabData;
+use amethyst::ecs::{Entity, WriteStorage};
+use amethyst::error::Error;
+
+impl<'a> PrefabData<'a> for DynamicBody {
+    type SystemData = WriteStorage<'a, DynamicBody>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        bodies: &mut Self::SystemData,
+        _: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        bodies.insert(entity, *self)?;
+        Ok(())
+    }
+}
+
+impl<'a> PrefabData<'a> for Collider {
+    type SystemData = WriteStorage<'a, Collider>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        colliders: &mut Self::SystemData,
+        _: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        colliders.insert(entity, self.clone())?;
+        Ok(())
+    }
+}
+
+impl<'a> PrefabData<'a> for Colliders {
+    type SystemData = WriteStorage<'a, Colliders>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        colliders: &mut Self::SystemData,
+        _: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        colliders.insert(entity, self.clone())?;
+        Ok(())
+    }
+}
+
+/// Prefab data attaching a `DynamicBody`, a `Collider` and/or `Colliders` to an entity.
+///
+/// The entity also needs a `Transform` and a `GlobalTransform` to be simulated, so this is
+/// usually part of a larger prefab data type next to a `Transform`. Colliders of child entities
+/// without a body are attached to the body of their nearest ancestor.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsPrefab {
+    pub body: Option<DynamicBody>,
+    pub collider: Option<Collider>,
+    pub colliders: Option<Colliders>,
+}
+
+impl<'a> PrefabData<'a> for PhysicsPrefab {
+    type SystemData = (
+        <DynamicBody as PrefabData<'a>>::SystemData,
+        <Collider as PrefabData<'a>>::SystemData,
+        <Colliders as PrefabData<'a>>::SystemData,
+    );
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        (bodies, colliders, multi_colliders): &mut Self::SystemData,
+        entities: &[Entity],
+        children: &[Entity],
+    ) -> Result<(), Error> {
+        if let Some(body) = &self.body {
+            body.add_to_entity(entity, bodies, entities, children)?;
+        }
+        if let Some(collider) = &self.collider {
+            collider.add_to_entity(entity, colliders, entities, children)?;
+        }
+        if let Some(multi_collider) = &self.colliders {
+            multi_collider.add_to_entity(entity, multi_colliders, entities, children)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use crate::handles::PhysicsHandles;
+use crate::PhysicsWorld;
+use amethyst::ecs::{Entity, Read, ReadExpect, Resources, SystemData};
+use amethyst::shred::ResourceId;
+use ncollide::bounding_volume::{BoundingVolume, AABB};
+use ncollide::query::{self, Ray};
+use ncollide::shape::Shape;
+use ncollide::world::CollisionGroups;
+use nphysics::math::{Isometry, Point, Vector};
+use nphysics::object::ColliderHandle;
+use std::cmp::Ordering;
+use std::f32::EPSILON;
+
+/// A collider hit by a ray.
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub collider: ColliderHandle,
+    /// Time of impact, in multiples of the ray direction.
+    pub toi: f32,
+    /// Distance between the ray origin and the hit point.
+    pub distance: f32,
+    pub point: Point<f32>,
+    /// Surface normal at the hit point.
+    pub normal: Vector<f32>,
+}
+
+/// A collider hit by a moving shape.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapeCastHit {
+    pub entity: Entity,
+    pub collider: ColliderHandle,
+    /// Time of impact, in multiples of the shape velocity.
+    pub toi: f32,
+    /// First point of contact on the hit collider.
+    pub point: Point<f32>,
+    /// Surface normal of the hit collider at the contact point.
+    pub normal: Vector<f32>,
+}
+
+/// `SystemData` to query the physics world, resolving the colliders found back to their entities.
+///
+/// Only colliders synchronized from a `Collider` component are reported.
+pub struct PhysicsQuery<'a> {
+    world: ReadExpect<'a, PhysicsWorld>,
+    handles: Read<'a, PhysicsHandles>,
+}
+
+type PhysicsQueryData<'a> = (ReadExpect<'a, PhysicsWorld>, Read<'a, PhysicsHandles>);
+
+impl<'a> SystemData<'a> for PhysicsQuery<'a> {
+    fn setup(res: &mut Resources) {
+        PhysicsQueryData::setup(res);
+    }
+
+    fn fetch(res: &'a Resources) -> Self {
+        let (world, handles) = PhysicsQueryData::fetch(res);
+        PhysicsQuery { world, handles }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        PhysicsQueryData::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        PhysicsQueryData::writes()
+    }
+}
+
+impl<'a> PhysicsQuery<'a> {
+    pub fn world(&self) -> &PhysicsWorld {
+        &self.world
+    }
+
+    pub fn handles(&self) -> &PhysicsHandles {
+        &self.handles
+    }
+
+    /// Closest collider hit by the ray, within `max_toi` multiples of the ray direction.
+    pub fn cast_ray(
+        &self,
+        ray: &Ray<f32>,
+        max_toi: f32,
+        groups: &CollisionGroups,
+    ) -> Option<RayHit> {
+        self.ray_hits(ray, max_toi, groups)
+            .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal))
+    }
+
+    /// All colliders hit by the ray, within `max_toi` multiples of the ray direction, closest first.
+    pub fn cast_ray_all(
+        &self,
+        ray: &Ray<f32>,
+        max_toi: f32,
+        groups: &CollisionGroups,
+    ) -> Vec<RayHit> {
+        let mut hits = self.ray_hits(ray, max_toi, groups).collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal));
+        hits
+    }
+
+    fn ray_hits<'b>(
+        &'b self,
+        ray: &'b Ray<f32>,
+        max_toi: f32,
+        groups: &'b CollisionGroups,
+    ) -> impl Iterator<Item = RayHit> + 'b {
+        let direction_length = ray.dir.norm();
+        self.world
+            .collider_world()
+            .interferences_with_ray(ray, groups)
+            .filter(move |(_, intersection)| intersection.toi <= max_toi)
+            .filter_map(move |(collider, intersection)| {
+                let entity = self.handles.collider_entity(collider.handle())?;
+                Some(RayHit {
+                    entity,
+                    collider: collider.handle(),
+                    toi: intersection.toi,
+                    distance: intersection.toi * direction_length,
+                    point: ray.point_at(intersection.toi),
+                    normal: intersection.normal,
+                })
+            })
+    }
+
+    /// First collider hit by `shape` when moving it from `position` along `velocity`, within
+    /// `max_toi` multiples of `velocity`.
+    pub fn cast_shape(
+        &self,
+        shape: &dyn Shape<f32>,
+        position: &Isometry<f32>,
+        velocity: &Vector<f32>,
+        max_toi: f32,
+        groups: &CollisionGroups,
+    ) -> Option<ShapeCastHit> {
+        let start = shape.aabb(position);
+        let end = AABB::new(
+            start.mins() + velocity * max_toi,
+            start.maxs() + velocity * max_toi,
+        );
+        let swept = start.merged(&end);
+        let prediction = self.world.prediction();
+
+        self.world
+            .collider_world()
+            .interferences_with_aabb(&swept, groups)
+            .filter_map(|collider| {
+                let entity = self.handles.collider_entity(collider.handle())?;
+                let toi = query::time_of_impact(
+                    position,
+                    velocity,
+                    shape,
+                    collider.position(),
+                    &Vector::zeros(),
+                    collider.shape().as_ref(),
+                )?;
+                if toi > max_toi {
+                    return None;
+                }
+
+                let mut position_at_impact = *position;
+                position_at_impact.translation.vector += velocity * toi;
+                let (point, normal) = impact_point(
+                    shape,
+                    &position_at_impact,
+                    velocity,
+                    collider.shape().as_ref(),
+                    collider.position(),
+                    prediction,
+                );
+
+                Some(ShapeCastHit {
+                    entity,
+                    collider: collider.handle(),
+                    toi,
+                    point,
+                    normal,
+                })
+            })
+            .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal))
+    }
+
+    /// Entities having a collider that contains the point.
+    pub fn entities_containing_point(
+        &self,
+        point: &Point<f32>,
+        groups: &CollisionGroups,
+    ) -> Vec<Entity> {
+        unique_entities(
+            self.world
+                .collider_world()
+                .interferences_with_point(point, groups)
+                .filter_map(|collider| self.handles.collider_entity(collider.handle())),
+        )
+    }
+
+    /// Entities having a collider whose bounding box overlaps the given one.
+    pub fn entities_overlapping_aabb(
+        &self,
+        aabb: &AABB<f32>,
+        groups: &CollisionGroups,
+    ) -> Vec<Entity> {
+        unique_entities(
+            self.world
+                .collider_world()
+                .interferences_with_aabb(aabb, groups)
+                .filter_map(|collider| self.handles.collider_entity(collider.handle())),
+        )
+    }
+}
+
+/// Point and normal of the hit collider where the cast shape touches it.
+///
+/// The time of impact is only computed up to a tolerance, so the shapes can still be farther apart
+/// than the prediction distance at the impact pose: the contact is then searched up to their actual
+/// distance. Should no contact be found even then, the position of the cast shape and the opposite
+/// of its velocity are used instead, so that the hit is still reported.
+fn impact_point(
+    shape: &dyn Shape<f32>,
+    position: &Isometry<f32>,
+    velocity: &Vector<f32>,
+    hit_shape: &dyn Shape<f32>,
+    hit_position: &Isometry<f32>,
+    prediction: f32,
+) -> (Point<f32>, Vector<f32>) {
+    query::contact(position, shape, hit_position, hit_shape, prediction)
+        .or_else(|| {
+            let distance = query::distance(position, shape, hit_position, hit_shape);
+            query::contact(
+                position,
+                shape,
+                hit_position,
+                hit_shape,
+                distance + prediction + EPSILON,
+            )
+        })
+        .map(|contact| (contact.world2, -contact.normal.into_inner()))
+        .unwrap_or_else(|| {
+            let normal = velocity
+                .try_normalize(EPSILON)
+                .map_or_else(Vector::zeros, |direction| -direction);
+            (Point::from(position.translation.vector), normal)
+        })
+}
+
+/// Entities can own several colliders, only report each of them once.
+fn unique_entities(entities: impl Iterator<Item = Entity>) -> Vec<Entity> {
+    let mut entities = entities.collect::<Vec<_>>();
+    entities.sort();
+    entities.dedup();
+    entities
+}
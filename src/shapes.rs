@@ -0,0 +1,373 @@
+use nalgebra::{DMatrix, Isometry3, Matrix3, Point3, Vector3};
+use ncollide::procedural;
+use ncollide::shape::{
+    Ball, Capsule, Compound, ConvexHull, Cuboid, HeightField, Shape, ShapeHandle, TriMesh,
+};
+use nphysics::volumetric::Volumetric;
+use std::f32::consts::PI;
+
+/// Number of subdivisions used when approximating round shapes with convex hulls.
+const ROUND_SHAPE_SUBDIVISIONS: u32 = 16;
+
+/// Serializable description of a collision shape, built into a `ShapeHandle` when the `Collider`
+/// is inserted into the physics world.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum ShapeDesc {
+    /// A sphere.
+    Ball { radius: f32 },
+    /// A box, given by half of its extents along each axis.
+    Cuboid { half_extents: Vector3<f32> },
+    /// A capsule aligned with the `y` axis.
+    Capsule { half_height: f32, radius: f32 },
+    /// A cylinder aligned with the `y` axis. Approximated by a convex hull.
+    Cylinder { half_height: f32, radius: f32 },
+    /// A cone aligned with the `y` axis, its apex pointing up. Approximated by a convex hull.
+    Cone { half_height: f32, radius: f32 },
+    /// The convex hull of the given points.
+    ConvexHull { points: Vec<Point3<f32>> },
+    /// A triangle mesh. Every index triplet describes one triangle.
+    TriMesh {
+        points: Vec<Point3<f32>>,
+        indices: Vec<Point3<usize>>,
+    },
+    /// A heightfield lying on the `xz` plane. `heights` is given row by row, all rows must have the
+    /// same length.
+    HeightField {
+        heights: Vec<Vec<f32>>,
+        scale: Vector3<f32>,
+    },
+    /// A set of shapes, each placed relative to the collider.
+    Compound {
+        shapes: Vec<(Isometry3<f32>, ShapeDesc)>,
+    },
+    /// An already built shape. Can't be serialized.
+    #[serde(skip)]
+    Custom(ShapeHandle<f32>),
+}
+
+/// Error when trying to build a `ShapeHandle` from a `ShapeDesc`.
+#[derive(Debug)]
+pub enum ShapeDescError {
+    /// The points of a convex hull don't enclose any volume.
+    DegenerateConvexHull,
+    /// The rows of a heightfield don't have the same length.
+    RaggedHeightField,
+    /// A triangle of a triangle mesh references a point that doesn't exist.
+    TriMeshIndexOutOfBounds(usize),
+    /// A triangle mesh has no triangles.
+    EmptyTriMesh,
+    /// A dimension of a shape, named by the first field, isn't strictly positive.
+    NonPositiveDimension(&'static str, f32),
+    /// A heightfield has less than 2 rows or 2 columns.
+    HeightFieldTooSmall { rows: usize, columns: usize },
+    /// A compound shape has no shapes.
+    EmptyCompound,
+}
+
+impl std::fmt::Display for ShapeDescError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShapeDescError::DegenerateConvexHull => {
+                write!(f, "Failed to compute the convex hull of the given points!")
+            }
+            ShapeDescError::RaggedHeightField => {
+                write!(f, "All rows of a heightfield must have the same length!")
+            }
+            ShapeDescError::TriMeshIndexOutOfBounds(index) => {
+                write!(f, "Triangle mesh index {} is out of bounds!", index)
+            }
+            ShapeDescError::EmptyTriMesh => write!(f, "Triangle mesh has no triangles!"),
+            ShapeDescError::NonPositiveDimension(name, value) => write!(
+                f,
+                "Shape dimension `{}` must be strictly positive, got {}!",
+                name, value
+            ),
+            ShapeDescError::HeightFieldTooSmall { rows, columns } => write!(
+                f,
+                "Heightfield must have at least 2 rows and 2 columns, got {}x{}!",
+                rows, columns
+            ),
+            ShapeDescError::EmptyCompound => write!(f, "Compound shape has no shapes!"),
+        }
+    }
+}
+
+impl std::fmt::Debug for ShapeDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShapeDesc::Ball { radius } => write!(f, "Ball {{ radius: {} }}", radius),
+            ShapeDesc::Cuboid { half_extents } => {
+                write!(f, "Cuboid {{ half_extents: {} }}", half_extents)
+            }
+            ShapeDesc::Capsule {
+                half_height,
+                radius,
+            } => write!(
+                f,
+                "Capsule {{ half_height: {}, radius: {} }}",
+                half_height, radius
+            ),
+            ShapeDesc::Cylinder {
+                half_height,
+                radius,
+            } => write!(
+                f,
+                "Cylinder {{ half_height: {}, radius: {} }}",
+                half_height, radius
+            ),
+            ShapeDesc::Cone {
+                half_height,
+                radius,
+            } => write!(
+                f,
+                "Cone {{ half_height: {}, radius: {} }}",
+                half_height, radius
+            ),
+            ShapeDesc::ConvexHull { points } => {
+                write!(f, "ConvexHull {{ {} points }}", points.len())
+            }
+            ShapeDesc::TriMesh { points, indices } => write!(
+                f,
+                "TriMesh {{ {} points, {} triangles }}",
+                points.len(),
+                indices.len()
+            ),
+            ShapeDesc::HeightField { heights, scale } => write!(
+                f,
+                "HeightField {{ {} rows, scale: {} }}",
+                heights.len(),
+                scale
+            ),
+            ShapeDesc::Compound { shapes } => {
+                f.debug_struct("Compound").field("shapes", shapes).finish()
+            }
+            ShapeDesc::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl ShapeDesc {
+    /// Builds the collision shape described by this value.
+    pub fn build(&self) -> Result<ShapeHandle<f32>, ShapeDescError> {
+        Ok(match self {
+            ShapeDesc::Ball { radius } => {
+                positive("radius", *radius)?;
+                ShapeHandle::new(Ball::new(*radius))
+            }
+            ShapeDesc::Cuboid { half_extents } => {
+                for half_extent in half_extents.iter() {
+                    positive("half_extents", *half_extent)?;
+                }
+                ShapeHandle::new(Cuboid::new(*half_extents))
+            }
+            ShapeDesc::Capsule {
+                half_height,
+                radius,
+            } => {
+                positive("half_height", *half_height)?;
+                positive("radius", *radius)?;
+                ShapeHandle::new(Capsule::new(*half_height, *radius))
+            }
+            ShapeDesc::Cylinder {
+                half_height,
+                radius,
+            } => {
+                positive("half_height", *half_height)?;
+                positive("radius", *radius)?;
+                let mesh = procedural::cylinder(
+                    *radius * 2.0,
+                    *half_height * 2.0,
+                    ROUND_SHAPE_SUBDIVISIONS,
+                );
+                convex_hull(&mesh.coords)?
+            }
+            ShapeDesc::Cone {
+                half_height,
+                radius,
+            } => {
+                positive("half_height", *half_height)?;
+                positive("radius", *radius)?;
+                let mesh =
+                    procedural::cone(*radius * 2.0, *half_height * 2.0, ROUND_SHAPE_SUBDIVISIONS);
+                convex_hull(&mesh.coords)?
+            }
+            ShapeDesc::ConvexHull { points } => convex_hull(points)?,
+            ShapeDesc::TriMesh { points, indices } => {
+                if indices.is_empty() {
+                    return Err(ShapeDescError::EmptyTriMesh);
+                }
+                if let Some(index) = indices
+                    .iter()
+                    .flat_map(|triangle| triangle.iter())
+                    .find(|index| **index >= points.len())
+                {
+                    return Err(ShapeDescError::TriMeshIndexOutOfBounds(*index));
+                }
+                ShapeHandle::new(TriMesh::new(points.clone(), indices.clone(), None))
+            }
+            ShapeDesc::HeightField { heights, scale } => {
+                let columns = heights.first().map(Vec::len).unwrap_or(0);
+                if heights.iter().any(|row| row.len() != columns) {
+                    return Err(ShapeDescError::RaggedHeightField);
+                }
+                if heights.len() < 2 || columns < 2 {
+                    return Err(ShapeDescError::HeightFieldTooSmall {
+                        rows: heights.len(),
+                        columns,
+                    });
+                }
+                for scale in scale.iter() {
+                    positive("scale", *scale)?;
+                }
+                let heights = DMatrix::from_fn(heights.len(), columns, |i, j| heights[i][j]);
+                ShapeHandle::new(HeightField::new(heights, *scale))
+            }
+            ShapeDesc::Compound { shapes } => {
+                if shapes.is_empty() {
+                    return Err(ShapeDescError::EmptyCompound);
+                }
+                let shapes = shapes
+                    .iter()
+                    .map(|(position, shape)| shape.build().map(|shape| (*position, shape)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                ShapeHandle::new(Compound::new(shapes))
+            }
+            ShapeDesc::Custom(shape) => shape.clone(),
+        })
+    }
+
+    /// Whether mass properties can be computed for this shape. Triangle meshes and heightfields
+    /// have no volume, and custom shapes are checked against the supported shapes.
+    pub fn is_volumetric(&self) -> bool {
+        match self {
+            ShapeDesc::TriMesh { .. } | ShapeDesc::HeightField { .. } => false,
+            ShapeDesc::Compound { shapes } => shapes.iter().all(|(_, shape)| shape.is_volumetric()),
+            ShapeDesc::Custom(shape) => is_volumetric_shape(&**shape),
+            _ => true,
+        }
+    }
+}
+
+impl From<ShapeHandle<f32>> for ShapeDesc {
+    fn from(shape: ShapeHandle<f32>) -> ShapeDesc {
+        ShapeDesc::Custom(shape)
+    }
+}
+
+/// Mass, center of mass and angular inertia of a shape, the latter relative to its center of mass.
+pub(crate) type MassProperties = (f32, Point3<f32>, Matrix3<f32>);
+
+/// Whether mass properties can be computed for the shape. nphysics supports balls, cuboids and
+/// convex hulls, capsules and compound shapes are handled by `shape_mass_properties`.
+pub(crate) fn is_volumetric_shape(shape: &Shape<f32>) -> bool {
+    if let Some(compound) = shape.as_shape::<Compound<f32>>() {
+        return compound
+            .shapes()
+            .iter()
+            .all(|(_, shape)| is_volumetric_shape(&**shape));
+    }
+    shape.is_shape::<Ball<f32>>()
+        || shape.is_shape::<Cuboid<f32>>()
+        || shape.is_shape::<Capsule<f32>>()
+        || shape.is_shape::<ConvexHull<f32>>()
+}
+
+/// Mass properties of the shape for the given density, in its local space. Returns `None` if the
+/// shape has no volume.
+pub(crate) fn shape_mass_properties(shape: &Shape<f32>, density: f32) -> Option<MassProperties> {
+    if !is_volumetric_shape(shape) {
+        return None;
+    }
+    if let Some(compound) = shape.as_shape::<Compound<f32>>() {
+        let parts = compound
+            .shapes()
+            .iter()
+            .map(|(position, shape)| {
+                shape_mass_properties(&**shape, density)
+                    .map(|part| transform_mass_properties(position, part))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        return Some(combine_mass_properties(&parts));
+    }
+    if let Some(capsule) = shape.as_shape::<Capsule<f32>>() {
+        return Some(capsule_mass_properties(
+            capsule.half_height(),
+            capsule.radius(),
+            density,
+        ));
+    }
+    Some(shape.mass_properties(density))
+}
+
+/// Expresses mass properties in the space the given position is relative to.
+pub(crate) fn transform_mass_properties(
+    position: &Isometry3<f32>,
+    (mass, center_of_mass, angular_inertia): MassProperties,
+) -> MassProperties {
+    let rotation = position.rotation.to_rotation_matrix();
+    (
+        mass,
+        position * center_of_mass,
+        rotation.matrix() * angular_inertia * rotation.matrix().transpose(),
+    )
+}
+
+/// Mass properties of several parts expressed in the same space, as a single body.
+pub(crate) fn combine_mass_properties(parts: &[MassProperties]) -> MassProperties {
+    let mass: f32 = parts.iter().map(|(mass, _, _)| mass).sum();
+    let center_of_mass = if mass > 0.0 {
+        Point3::from(
+            parts.iter().fold(Vector3::zeros(), |acc, (mass, com, _)| {
+                acc + com.coords * *mass
+            }) / mass,
+        )
+    } else {
+        Point3::origin()
+    };
+
+    // Move every angular inertia to the shared center of mass (parallel axis theorem).
+    let angular_inertia = parts
+        .iter()
+        .fold(Matrix3::zeros(), |acc, (part_mass, com, angular)| {
+            let shift = com - center_of_mass;
+            acc + angular
+                + (Matrix3::identity() * shift.norm_squared() - shift * shift.transpose())
+                    * *part_mass
+        });
+
+    (mass, center_of_mass, angular_inertia)
+}
+
+/// Mass properties of a capsule aligned with the `y` axis: a cylinder capped by two hemispheres.
+fn capsule_mass_properties(half_height: f32, radius: f32, density: f32) -> MassProperties {
+    let r2 = radius * radius;
+    let cylinder_mass = density * PI * r2 * half_height * 2.0;
+    let spheres_mass = density * PI * r2 * radius * 4.0 / 3.0;
+
+    let axial = cylinder_mass * r2 / 2.0 + spheres_mass * r2 * 2.0 / 5.0;
+    // The center of mass of each hemisphere is `3 / 8` of the radius away from its base.
+    let transverse = cylinder_mass * (r2 / 4.0 + half_height * half_height / 3.0)
+        + spheres_mass
+            * (r2 * 2.0 / 5.0 + half_height * half_height + half_height * radius * 3.0 / 4.0);
+
+    (
+        cylinder_mass + spheres_mass,
+        Point3::origin(),
+        Matrix3::from_diagonal(&Vector3::new(transverse, axial, transverse)),
+    )
+}
+
+/// Checks that a dimension is strictly positive, which the ncollide constructors assert.
+fn positive(name: &'static str, value: f32) -> Result<(), ShapeDescError> {
+    if value > 0.0 {
+        Ok(())
+    } else {
+        Err(ShapeDescError::NonPositiveDimension(name, value))
+    }
+}
+
+fn convex_hull(points: &[Point3<f32>]) -> Result<ShapeHandle<f32>, ShapeDescError> {
+    ConvexHull::try_from_points(points)
+        .map(ShapeHandle::new)
+        .ok_or(ShapeDescError::DegenerateConvexHull)
+}
@@ -2,8 +2,11 @@ mod physics_stepper;
 mod sync_bodies_from_physics;
 mod sync_bodies_to_physics;
 mod sync_colliders_to_physics;
+mod sync_gravity_fields_to_physics;
 mod sync_gravity_to_physics;
+mod sync_joints_to_physics;
 
+use crate::interpolation::InterpolationMode;
 use amethyst::core::bundle::SystemBundle;
 use amethyst::core::ecs::DispatcherBuilder;
 use amethyst::error::Error;
@@ -13,17 +16,22 @@ pub use self::physics_stepper::*;
 pub use self::sync_bodies_from_physics::*;
 pub use self::sync_bodies_to_physics::SyncBodiesToPhysicsSystem;
 pub use self::sync_colliders_to_physics::SyncCollidersToPhysicsSystem;
+pub use self::sync_gravity_fields_to_physics::SyncGravityFieldsToPhysicsSystem;
 pub use self::sync_gravity_to_physics::SyncGravityToPhysicsSystem;
+pub use self::sync_joints_to_physics::SyncJointsToPhysicsSystem;
 
 pub const SYNC_BODIES_TO_PHYSICS_SYSTEM: &str = "sync_bodies_to_physics_system";
 pub const SYNC_GRAVITY_TO_PHYSICS_SYSTEM: &str = "sync_gravity_to_physics_system";
 pub const SYNC_COLLIDERS_TO_PHYSICS_SYSTEM: &str = "sync_colliders_to_physics_system";
+pub const SYNC_JOINTS_TO_PHYSICS_SYSTEM: &str = "sync_joints_to_physics_system";
+pub const SYNC_GRAVITY_FIELDS_TO_PHYSICS_SYSTEM: &str = "sync_gravity_fields_to_physics_system";
 pub const PHYSICS_STEPPER_SYSTEM: &str = "physics_stepper_system";
 pub const SYNC_BODIES_FROM_PHYSICS_SYSTEM: &str = "sync_bodies_from_physics_system";
 
 pub struct PhysicsBundle<'a> {
     dep: &'a [&'a str],
     timestep_iter_limit: i32,
+    interpolation: InterpolationMode,
 }
 
 impl Default for PhysicsBundle<'_> {
@@ -31,6 +39,7 @@ impl Default for PhysicsBundle<'_> {
         Self {
             dep: Default::default(),
             timestep_iter_limit: 10,
+            interpolation: InterpolationMode::default(),
         }
     }
 }
@@ -50,6 +59,13 @@ impl<'a> PhysicsBundle<'a> {
         self.timestep_iter_limit = timestep_iter_limit;
         self
     }
+
+    /// Set how transforms are computed from the poses of the physics world between two physics
+    /// timesteps. Can be changed at runtime through the `PhysicsInterpolation` resource.
+    pub fn with_interpolation(mut self, interpolation: InterpolationMode) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
 }
 
 impl<'a, 'b, 'c> SystemBundle<'a, 'b> for PhysicsBundle<'c> {
@@ -72,12 +88,30 @@ impl<'a, 'b, 'c> SystemBundle<'a, 'b> for PhysicsBundle<'c> {
         );
 
         builder.add(
-            PhysicsStepperSystem::new(self.timestep_iter_limit),
+            SyncJointsToPhysicsSystem::new(),
+            SYNC_JOINTS_TO_PHYSICS_SYSTEM,
+            &[SYNC_BODIES_TO_PHYSICS_SYSTEM],
+        );
+
+        builder.add(
+            SyncGravityFieldsToPhysicsSystem::new(),
+            SYNC_GRAVITY_FIELDS_TO_PHYSICS_SYSTEM,
+            &[
+                SYNC_BODIES_TO_PHYSICS_SYSTEM,
+                SYNC_GRAVITY_TO_PHYSICS_SYSTEM,
+            ],
+        );
+
+        builder.add(
+            PhysicsStepperSystem::new(self.timestep_iter_limit)
+                .with_interpolation(self.interpolation),
             PHYSICS_STEPPER_SYSTEM,
             &[
                 SYNC_BODIES_TO_PHYSICS_SYSTEM,
                 SYNC_GRAVITY_TO_PHYSICS_SYSTEM,
                 SYNC_COLLIDERS_TO_PHYSICS_SYSTEM,
+                SYNC_JOINTS_TO_PHYSICS_SYSTEM,
+                SYNC_GRAVITY_FIELDS_TO_PHYSICS_SYSTEM,
             ],
         );
 
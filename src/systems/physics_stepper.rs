@@ -1,28 +1,56 @@
+use crate::bodies::DynamicBody;
+use crate::contacts::{ContactImpulses, Contacts, MeasuredContactModel};
+use crate::control::PhysicsControl;
+use crate::events::{
+    ContactPoint, ContactState, EntityContactEvent, EntityProximityEvent, JointBrokenEvent,
+};
+use crate::handles::PhysicsHandles;
+use crate::interpolation::{InterpolationMode, PhysicsInterpolation};
+use crate::joints::Joint;
 use crate::time_step::TimeStep;
 use crate::PhysicsWorld;
 use amethyst::core::Time;
-use amethyst::ecs::{Entity, Read, System, Write, WriteExpect};
+use amethyst::ecs::{
+    Entities, Entity, Join, Read, Resources, System, SystemData, Write, WriteExpect, WriteStorage,
+};
 use amethyst::shrev::EventChannel;
+use nalgebra::{Isometry3, Translation3, UnitQuaternion};
+use ncollide3d::bounding_volume::{BoundingVolume, AABB};
 use ncollide3d::events::{ContactEvent, ProximityEvent};
+use ncollide3d::query::{self, Proximity};
+use ncollide3d::world::GeometricQueryType;
+use nphysics3d::math::{Vector, Velocity};
+use nphysics3d::object::{Body, BodyHandle, BodyStatus, Collider, ColliderHandle};
+use nphysics3d::solver::SignoriniCoulombPyramidModel;
+use std::collections::HashMap;
 use std::f32::EPSILON;
 use std::time::Instant;
 
-// TODO: why is this here
-// Might want to replace by better types.
-pub type EntityContactEvent = (Entity, Entity, ContactEvent);
-pub type EntityProximityEvent = (Entity, Entity, ProximityEvent);
-
 /// Falloff factor for calculating the moving average step time.
 const AVERAGE_STEP_TIME_FALLOFF: f32 = 0.33;
 /// Factor to apply to available physics time before decreasing the timestep. Makes sure that the
 /// timestep isn't switched too eagerly.
 const TIME_STEP_DECREASE_HYSTERESIS: f32 = 1.5;
 
+/// Collider pairs currently touching or overlapping, and the entities they belonged to when they
+/// started to.
+type CollidingPairs = HashMap<(ColliderHandle, ColliderHandle), (Entity, Entity)>;
+
 /// Simulates a step of the physics world.
+///
+/// Replaces the contact model of the physics world when set up, with the default model of nphysics
+/// recording the impulses reported by `EntityContactEvent`s.
 pub struct PhysicsStepperSystem {
     timestep_iter_limit: i32,
     time_accumulator: f32,
     avg_step_time: Option<f32>,
+    interpolation_mode: InterpolationMode,
+    /// Touching pairs, to resolve the entities of stopped contacts after a collider is removed.
+    contact_pairs: CollidingPairs,
+    /// Same as `contact_pairs`, for intersecting triggers.
+    proximity_pairs: CollidingPairs,
+    /// Contact impulses of the last step, written by the contact model of the physics world.
+    contact_impulses: ContactImpulses,
 }
 
 impl Default for PhysicsStepperSystem {
@@ -31,6 +59,10 @@ impl Default for PhysicsStepperSystem {
             timestep_iter_limit: 10,
             time_accumulator: 0.,
             avg_step_time: None,
+            interpolation_mode: InterpolationMode::default(),
+            contact_pairs: HashMap::new(),
+            proximity_pairs: HashMap::new(),
+            contact_impulses: ContactImpulses::default(),
         }
     }
 }
@@ -41,8 +73,19 @@ impl PhysicsStepperSystem {
             timestep_iter_limit,
             time_accumulator: 0.,
             avg_step_time: None,
+            interpolation_mode: InterpolationMode::default(),
+            contact_pairs: HashMap::new(),
+            proximity_pairs: HashMap::new(),
+            contact_impulses: ContactImpulses::default(),
         }
     }
+
+    /// Set the initial `InterpolationMode` of the `PhysicsInterpolation` resource, if it doesn't
+    /// exist yet when this system is set up.
+    pub fn with_interpolation(mut self, interpolation_mode: InterpolationMode) -> Self {
+        self.interpolation_mode = interpolation_mode;
+        self
+    }
 }
 
 impl<'a> System<'a> for PhysicsStepperSystem {
@@ -52,6 +95,14 @@ impl<'a> System<'a> for PhysicsStepperSystem {
         Write<'a, TimeStep>,
         Write<'a, EventChannel<EntityContactEvent>>,
         Write<'a, EventChannel<EntityProximityEvent>>,
+        Entities<'a>,
+        WriteStorage<'a, DynamicBody>,
+        Write<'a, PhysicsHandles>,
+        WriteStorage<'a, Contacts>,
+        Write<'a, PhysicsInterpolation>,
+        WriteStorage<'a, Joint>,
+        Write<'a, EventChannel<JointBrokenEvent>>,
+        Write<'a, PhysicsControl>,
     );
 
     // Simulate world using the current time frame
@@ -62,8 +113,64 @@ impl<'a> System<'a> for PhysicsStepperSystem {
             mut intended_timestep,
             mut contact_events,
             mut proximity_events,
+            entities,
+            mut physics_bodies,
+            mut handles,
+            mut contacts,
+            mut interpolation,
+            mut joints,
+            mut joint_broken_events,
+            mut control,
         ) = data;
 
+        // Components inserted since the last frame start with the pairs already touching, which
+        // no event reports anymore.
+        for (entity, contacts) in (&entities, &mut contacts).join() {
+            contacts.begin_frame(&entities);
+            contacts.seed(
+                entity,
+                self.contact_pairs
+                    .values()
+                    .chain(self.proximity_pairs.values())
+                    .cloned(),
+            );
+        }
+
+        // Colliders removed by the synchronization systems, or rebuilt for another entity, stop
+        // touching everything. Their stopped events are reported here, as their entities can't be
+        // resolved from nphysics events anymore.
+        let stopped_contacts = take_stale_pairs(&mut self.contact_pairs, &handles)
+            .into_iter()
+            .map(|((collider1, collider2), (entity1, entity2))| {
+                update_contacts(&mut contacts, entity1, entity2, false);
+                EntityContactEvent {
+                    entity1,
+                    entity2,
+                    collider1,
+                    collider2,
+                    state: ContactState::Stopped,
+                    contacts: Vec::new(),
+                    impulse: 0.0,
+                }
+            })
+            .collect::<Vec<_>>();
+        contact_events.iter_write(stopped_contacts.into_iter());
+
+        let stopped_proximities = take_stale_pairs(&mut self.proximity_pairs, &handles)
+            .into_iter()
+            .map(|((collider1, collider2), (entity1, entity2))| {
+                update_contacts(&mut contacts, entity1, entity2, false);
+                let event = ProximityEvent::new(
+                    collider1,
+                    collider2,
+                    Proximity::Intersecting,
+                    Proximity::Disjoint,
+                );
+                (entity1, entity2, event)
+            })
+            .collect::<Vec<_>>();
+        proximity_events.iter_write(stopped_proximities.into_iter());
+
         let (timestep, mut change_timestep) = match &mut *intended_timestep {
             TimeStep::Fixed(timestep) => (*timestep, false),
             TimeStep::SemiFixed(constraint) => {
@@ -73,8 +180,8 @@ impl<'a> System<'a> for PhysicsStepperSystem {
                     // As simulated time is affected by the time scale, simulated time step / time scale
                     // is the maximum real time the step may take, so we take that into account here. We
                     // also take into account the maximum fraction of time physics are allowed to take
-                    let adjusted_step_time =
-                        avg_step * time.time_scale() / constraint.max_physics_time_fraction();
+                    let adjusted_step_time = avg_step * time.time_scale() * control.time_scale()
+                        / constraint.max_physics_time_fraction();
                     constraint.set_running_slow(constraint.current_timestep() < adjusted_step_time);
                     if constraint.should_increase_timestep() {
                         match constraint.increase_timestep() {
@@ -123,8 +230,21 @@ impl<'a> System<'a> for PhysicsStepperSystem {
             physical_world.set_timestep(timestep);
         }
 
-        self.time_accumulator += time.delta_seconds();
+        if !control.is_paused() {
+            self.time_accumulator += time.delta_seconds() * control.time_scale();
+        }
+        // Requested steps are added as whole timesteps, so that exactly that many more are taken.
+        let requested_steps = control.take_pending_steps();
+        if requested_steps > 0 {
+            trace!("Simulating {} requested physics steps", requested_steps);
+            self.time_accumulator += requested_steps as f32 * timestep;
+        }
         let mut steps = 0;
+        // Kinematic bodies reach their target pose by the end of the steps of this frame.
+        let planned_steps = ((self.time_accumulator / timestep) as i32)
+            .min(self.timestep_iter_limit + 1)
+            .max(1);
+        let mut motor_impulses = HashMap::new();
 
         while steps <= self.timestep_iter_limit && self.time_accumulator >= timestep {
             let physics_time = Instant::now();
@@ -136,65 +256,250 @@ impl<'a> System<'a> for PhysicsStepperSystem {
                 self.time_accumulator
             );
 
+            if interpolation.mode == InterpolationMode::Interpolate {
+                interpolation.clear_previous_positions();
+                for handle in (&physics_bodies).join().filter_map(|body| body.handle()) {
+                    if let Some(rigid_body) = physical_world.rigid_body(handle) {
+                        interpolation.record_previous_position(handle, *rigid_body.position());
+                    }
+                }
+            }
+
+            // Forces are cleared by nphysics after every step, so they have to be re-applied on
+            // each step of this frame. Impulses are only applied on the first one. The same goes
+            // for the gravity of bodies with a gravity scale.
+            let world_gravity = *physical_world.gravity();
+            for body in (&physics_bodies).join() {
+                if !body.has_pending_forces() && body.uses_world_gravity() {
+                    continue;
+                }
+                if let Some(rigid_body) = body
+                    .handle()
+                    .and_then(|handle| physical_world.rigid_body_mut(handle))
+                {
+                    if body.has_pending_forces() {
+                        body.apply_pending_forces(rigid_body, steps == 0);
+                    }
+                    body.apply_scaled_gravity(rigid_body, &world_gravity);
+                }
+            }
+
+            let remaining_time = (planned_steps - steps).max(1) as f32 * timestep;
+            for body in (&physics_bodies).join() {
+                if body.kinematic_target.is_none() {
+                    continue;
+                }
+                if let Some(rigid_body) = body
+                    .handle()
+                    .and_then(|handle| physical_world.rigid_body_mut(handle))
+                {
+                    body.apply_kinematic_velocity_to(rigid_body, remaining_time);
+                }
+            }
+
+            // Joint motors are applied as forces too.
+            for (entity, joint) in (&entities, &joints).join() {
+                if joint.motor.is_none() || handles.joint(entity).is_none() {
+                    continue;
+                }
+                if let Some((handle1, handle2)) = joint.body_handles(&handles) {
+                    let impulse =
+                        joint.apply_motor(&mut physical_world, handle1, handle2, timestep);
+                    *motor_impulses.entry(entity).or_insert(0.0) += impulse;
+                }
+            }
+
+            // Fast bodies are moved up to their first impact, so that the step resolves it instead
+            // of letting them go through.
+            let advanced_bodies = (&physics_bodies)
+                .join()
+                .filter(|body| body.ccd_enabled)
+                .filter_map(|body| {
+                    let handle = body.handle()?;
+                    advance_to_impact(&mut physical_world, handle, body.ccd_motion_threshold)
+                        .map(|fraction| (handle, fraction))
+                })
+                .collect::<Vec<_>>();
+
+            self.contact_impulses.clear();
             physical_world.step();
 
+            for (handle, fraction) in advanced_bodies {
+                rewind_advanced_motion(&mut physical_world, handle, fraction);
+            }
+
+            trace!("Checking breakable joints.");
+
+            let mut broken_joints = Vec::new();
+            for (entity, joint) in (&entities, &joints).join() {
+                if !joint.is_breakable() || joint.broken {
+                    continue;
+                }
+                let handle = match handles.joint(entity) {
+                    Some(handle) => handle,
+                    None => continue,
+                };
+                let (linear_impulse, angular_impulse) = joint.impulses(&physical_world, handle);
+
+                if joint.breaks_under(linear_impulse, angular_impulse) {
+                    broken_joints.push(JointBrokenEvent {
+                        joint: entity,
+                        entity1: joint.body1,
+                        entity2: joint.body2,
+                        linear_impulse,
+                        angular_impulse,
+                    });
+                }
+            }
+
+            for event in &broken_joints {
+                if let Some(joint) = joints.get_mut(event.joint) {
+                    joint.broken = true;
+                }
+                if let Some(handle) = handles.remove_joint(event.joint.id()) {
+                    trace!("Removing broken joint constraint: {:?}", handle);
+                    physical_world.remove_constraint(handle);
+                }
+            }
+
+            joint_broken_events.iter_write(broken_joints.into_iter());
+
             trace!("iterating collision events.");
 
             let collision_world = physical_world.collider_world();
+            let contact_pairs = &mut self.contact_pairs;
+            let contact_impulses = &self.contact_impulses;
 
-            let contact_ev = collision_world.contact_events().iter().cloned().flat_map(|ev| {
+            let contact_ev = collision_world
+                .contact_events()
+                .iter()
+                .cloned()
+                .flat_map(|ev| {
                     trace!("Emitting contact event: {:?}", ev);
 
-                    let (handle1, handle2) = match ev {
-                        ContactEvent::Started(h1, h2) => (h1, h2),
-                        ContactEvent::Stopped(h1, h2) => (h1, h2),
+                    let (handle1, handle2, state) = match ev {
+                        ContactEvent::Started(h1, h2) => (h1, h2, ContactState::Started),
+                        ContactEvent::Stopped(h1, h2) => (h1, h2, ContactState::Stopped),
                     };
-                    let coll1 = physical_world.collider(handle1);
-                    let coll2 = physical_world.collider(handle2);
-                    if let (Some(c1), Some(c2)) = (coll1, coll2) {
-                        // TODO: Check if the data is in fact the one we want. There might be
-                        // user-inserted one.
-                        let e1 = c1.user_data().map(|data| data.downcast_ref::<Entity>().unwrap());
-                        let e2 = c2.user_data().map(|data| data.downcast_ref::<Entity>().unwrap());
-                        if let (Some(e1), Some(e2)) = (e1, e2) {
-                            Some((*e1, *e2, ev))
-                        } else {
-                            error!("Failed to find entity for collider during proximity event iteration. Was the entity removed?");
-                            None
-                        }
-                    } else {
-                        error!("Failed to fetch the rigid body from the physical world using the collider handle of the collision event. Was the entity removed?.");
-                        None
-                    }
-                }).collect::<Vec<_>>();
+                    let (entity1, entity2) = match state {
+                        ContactState::Started => match (
+                            handles.collider_entity(handle1),
+                            handles.collider_entity(handle2),
+                        ) {
+                            (Some(e1), Some(e2)) => {
+                                if !start_pair(contact_pairs, handle1, handle2, (e1, e2)) {
+                                    trace!("Ignoring contact that already started: {:?}", ev);
+                                    return None;
+                                }
+                                (e1, e2)
+                            }
+                            _ => {
+                                error!("Failed to find entity for collider during contact event iteration. Was the entity removed?");
+                                return None;
+                            }
+                        },
+                        // Entities are resolved from the started pair, as the colliders may be
+                        // gone already.
+                        ContactState::Stopped => match stop_pair(contact_pairs, handle1, handle2) {
+                            Some(entities) => entities,
+                            None => {
+                                trace!("Ignoring contact already reported as stopped: {:?}", ev);
+                                return None;
+                            }
+                        },
+                    };
+
+                    let contacts = match state {
+                        ContactState::Started => collision_world
+                            .contact_pair(handle1, handle2, true)
+                            .map(|(_, _, _, manifold)| {
+                                manifold
+                                    .contacts()
+                                    .map(|tracked| ContactPoint {
+                                        world1: tracked.contact.world1,
+                                        world2: tracked.contact.world2,
+                                        normal: tracked.contact.normal.into_inner(),
+                                        depth: tracked.contact.depth,
+                                    })
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default(),
+                        ContactState::Stopped => Vec::new(),
+                    };
+
+                    Some(EntityContactEvent {
+                        entity1,
+                        entity2,
+                        collider1: handle1,
+                        collider2: handle2,
+                        state,
+                        contacts,
+                        impulse: contact_impulses.get(handle1, handle2),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for event in &contact_ev {
+                update_contacts(
+                    &mut contacts,
+                    event.entity1,
+                    event.entity2,
+                    event.state == ContactState::Started,
+                );
+            }
 
             contact_events.iter_write(contact_ev.into_iter());
 
+            let proximity_pairs = &mut self.proximity_pairs;
+            let mut changed_proximities = Vec::new();
             let proximity_ev = collision_world
-                    .proximity_events()
-                    .iter()
-                    .cloned()
-                    .flat_map(|ev| {
-                        trace!("Emitting proximity event: {:?}", ev);
-                        println!("hello there");
-                        let coll1 = physical_world.collider(ev.collider1);
-                        let coll2 = physical_world.collider(ev.collider2);
-                        if let (Some(c1), Some(c2)) = (coll1, coll2) {
-                            // TODO: Check if the data is in fact the one we want. There might be
-                            // user-inserted one.
-                            let e1 = c1.user_data().map(|data| data.downcast_ref::<Entity>().unwrap());
-                            let e2 = c2.user_data().map(|data| data.downcast_ref::<Entity>().unwrap());
-                            if let (Some(e1), Some(e2)) = (e1, e2) {
-                                Some((*e1, *e2, ev))
-                            } else {
-                                error!("Failed to find entity for collider during proximity event iteration. Was the entity removed?");
+                .proximity_events()
+                .iter()
+                .cloned()
+                .flat_map(|ev| {
+                    trace!("Emitting proximity event: {:?}", ev);
+
+                    let was_intersecting = ev.prev_status == Proximity::Intersecting;
+                    let is_intersecting = ev.new_status == Proximity::Intersecting;
+                    if was_intersecting && !is_intersecting {
+                        // Same as stopped contacts, the colliders may be gone already.
+                        return match stop_pair(proximity_pairs, ev.collider1, ev.collider2) {
+                            Some((e1, e2)) => {
+                                changed_proximities.push((e1, e2, false));
+                                Some((e1, e2, ev))
+                            }
+                            None => {
+                                trace!("Ignoring proximity already reported as stopped: {:?}", ev);
                                 None
                             }
-                        } else {
-                            error!("Failed to fetch the rigid body from the physical world using the collider handle of the collision event. Was the entity removed?.");
+                        };
+                    }
+
+                    match (
+                        handles.collider_entity(ev.collider1),
+                        handles.collider_entity(ev.collider2),
+                    ) {
+                        (Some(e1), Some(e2)) => {
+                            if is_intersecting && !was_intersecting {
+                                if !start_pair(proximity_pairs, ev.collider1, ev.collider2, (e1, e2)) {
+                                    trace!("Ignoring proximity that already started: {:?}", ev);
+                                    return None;
+                                }
+                                changed_proximities.push((e1, e2, true));
+                            }
+                            Some((e1, e2, ev))
+                        }
+                        _ => {
+                            error!("Failed to find entity for collider during proximity event iteration. Was the entity removed?");
                             None
                         }
-                    }).collect::<Vec<_>>();
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for (entity1, entity2, intersecting) in changed_proximities {
+                update_contacts(&mut contacts, entity1, entity2, intersecting);
+            }
 
             proximity_events.iter_write(proximity_ev.into_iter());
 
@@ -213,6 +518,53 @@ impl<'a> System<'a> for PhysicsStepperSystem {
             steps += 1;
         }
 
+        interpolation.set_leftover_time(self.time_accumulator, timestep);
+
+        let joint_entities = (&entities, &joints)
+            .join()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in joint_entities {
+            let impulse = motor_impulses.get(&entity).cloned().unwrap_or(0.0);
+            Joint::set_state_if_changed(&mut joints, entity, |state| state.motor_impulse = impulse);
+        }
+
+        if steps > 0 {
+            trace!("Clearing external forces applied during this frame.");
+            let bodies_with_forces = (&entities, &physics_bodies)
+                .join()
+                .filter(|(_, body)| body.has_pending_forces())
+                .map(|(entity, _)| entity)
+                .collect::<Vec<_>>();
+            for entity in bodies_with_forces {
+                if let Some(body) = physics_bodies.get_mut(entity) {
+                    body.clear_forces();
+                }
+            }
+
+            trace!("Stopping kinematic bodies that reached their target.");
+            let kinematic_bodies = (&entities, &physics_bodies)
+                .join()
+                .filter(|(_, body)| {
+                    body.kinematic_target.is_some() || body.body_status == BodyStatus::Kinematic
+                })
+                .map(|(entity, _)| entity)
+                .collect::<Vec<_>>();
+            for entity in kinematic_bodies {
+                if let Some(body) = physics_bodies.get_mut(entity) {
+                    body.kinematic_target = None;
+                    if let Some(rigid_body) = body
+                        .handle()
+                        .and_then(|handle| physical_world.rigid_body_mut(handle))
+                    {
+                        // The velocity the body moved with during the steps, zero if it didn't.
+                        body.velocity = *rigid_body.velocity();
+                        rigid_body.set_velocity(Velocity::zero());
+                    }
+                }
+            }
+        }
+
         trace!(
             "Average time per physics step: {:.8} seconds",
             self.avg_step_time.unwrap_or_default()
@@ -226,4 +578,193 @@ impl<'a> System<'a> for PhysicsStepperSystem {
             warn!("Physics running slow!");
         }
     }
+
+    fn setup(&mut self, res: &mut Resources) {
+        let interpolation_mode = self.interpolation_mode;
+        res.entry::<PhysicsInterpolation>()
+            .or_insert_with(|| PhysicsInterpolation::new(interpolation_mode));
+
+        // The default contact model of nphysics, recording the impulses of contact events.
+        res.entry::<PhysicsWorld>()
+            .or_insert_with(PhysicsWorld::new)
+            .set_contact_model(MeasuredContactModel::new(
+                SignoriniCoulombPyramidModel::new(),
+                self.contact_impulses.clone(),
+            ));
+
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Continuous collision detection for a fast body. If its colliders would hit another collider
+/// during the next step, the body is moved right before the impact while keeping its velocity, so
+/// that the step finds the contact and resolves it. Returns the fraction of the motion of the step
+/// the body was moved by.
+///
+/// Only the linear motion of the body is swept: its rotation is unchecked, so a long body spinning
+/// fast can still tunnel.
+fn advance_to_impact(
+    world: &mut PhysicsWorld,
+    handle: BodyHandle,
+    motion_threshold: f32,
+) -> Option<f32> {
+    let timestep = world.timestep();
+    let (position, motion) = match world.rigid_body(handle) {
+        Some(rigid_body) if rigid_body.is_dynamic() && rigid_body.is_active() => (
+            *rigid_body.position(),
+            rigid_body.velocity().linear * timestep,
+        ),
+        _ => return None,
+    };
+    let distance = motion.norm();
+    if distance <= motion_threshold || distance < EPSILON {
+        return None;
+    }
+
+    let collider_world = world.collider_world();
+    let mut time_of_impact: f32 = 1.0;
+    for collider in collider_world
+        .colliders()
+        .filter(|collider| collider.body() == handle && is_solid(collider))
+    {
+        let aabb = collider.shape().aabb(collider.position());
+        let swept_aabb = aabb.merged(&AABB::new(aabb.mins() + motion, aabb.maxs() + motion));
+        for other in
+            collider_world.interferences_with_aabb(&swept_aabb, collider.collision_groups())
+        {
+            if other.body() == handle || !is_solid(other) {
+                continue;
+            }
+            let other_motion = world
+                .rigid_body(other.body())
+                .map_or(Vector::zeros(), |rigid_body| {
+                    rigid_body.velocity().linear * timestep
+                });
+            if let Some(toi) = query::time_of_impact(
+                collider.position(),
+                &motion,
+                &**collider.shape(),
+                other.position(),
+                &other_motion,
+                &**other.shape(),
+            ) {
+                time_of_impact = time_of_impact.min(toi);
+            }
+        }
+    }
+
+    if time_of_impact >= 1.0 {
+        return None;
+    }
+
+    // Stop short of the impact, within the prediction distance of the contacts.
+    let advance = (distance * time_of_impact - world.prediction() * 0.25).max(0.0);
+    trace!(
+        "Moving fast body {:?} by {} to its impact, out of a motion of {}",
+        handle,
+        advance,
+        distance
+    );
+    let rigid_body = world.rigid_body_mut(handle)?;
+    let mut position = position;
+    position.translation.vector += motion / distance * advance;
+    rigid_body.set_position(position);
+    Some(advance / distance)
+}
+
+/// Takes the part of the step a fast body covered when it was moved to its impact back out of the
+/// motion of the step. The body only moves with its new velocity for the rest of the step, instead
+/// of for a whole timestep on top of the distance it was moved by.
+fn rewind_advanced_motion(world: &mut PhysicsWorld, handle: BodyHandle, fraction: f32) {
+    let time = -fraction * world.timestep();
+    let rigid_body = match world.rigid_body_mut(handle) {
+        Some(rigid_body) => rigid_body,
+        None => return,
+    };
+    let velocity = *rigid_body.velocity();
+    let position = *rigid_body.position();
+
+    // nphysics moves the center of mass along the linear velocity and rotates around it.
+    let center_of_mass = rigid_body.center_of_mass();
+    let rotation = UnitQuaternion::new(velocity.angular * time);
+    let translation = center_of_mass.coords
+        + velocity.linear * time
+        + rotation * (position.translation.vector - center_of_mass.coords);
+    rigid_body.set_position(Isometry3::from_parts(
+        Translation3::from(translation),
+        rotation * position.rotation,
+    ));
+}
+
+/// Whether the collider generates contacts, as opposed to a trigger.
+fn is_solid(collider: &Collider<f32>) -> bool {
+    match collider.query_type() {
+        GeometricQueryType::Contacts(..) => true,
+        GeometricQueryType::Proximity(_) => false,
+    }
+}
+
+/// Records a pair of colliders that started touching. Returns `false` if it already was.
+fn start_pair(
+    pairs: &mut CollidingPairs,
+    collider1: ColliderHandle,
+    collider2: ColliderHandle,
+    entities: (Entity, Entity),
+) -> bool {
+    if pairs.contains_key(&(collider1, collider2)) || pairs.contains_key(&(collider2, collider1)) {
+        return false;
+    }
+    pairs.insert((collider1, collider2), entities);
+    true
+}
+
+/// Forgets a pair of colliders that stopped touching, returning the entities they belonged to, in
+/// the order of the given colliders.
+fn stop_pair(
+    pairs: &mut CollidingPairs,
+    collider1: ColliderHandle,
+    collider2: ColliderHandle,
+) -> Option<(Entity, Entity)> {
+    pairs.remove(&(collider1, collider2)).or_else(|| {
+        pairs
+            .remove(&(collider2, collider1))
+            .map(|(entity2, entity1)| (entity1, entity2))
+    })
+}
+
+/// Removes the pairs of which a collider isn't owned by the same entity anymore.
+fn take_stale_pairs(
+    pairs: &mut CollidingPairs,
+    handles: &PhysicsHandles,
+) -> Vec<((ColliderHandle, ColliderHandle), (Entity, Entity))> {
+    let stale = pairs
+        .iter()
+        .filter(|((collider1, collider2), (entity1, entity2))| {
+            handles.collider_entity(*collider1) != Some(*entity1)
+                || handles.collider_entity(*collider2) != Some(*entity2)
+        })
+        .map(|(colliders, entities)| (*colliders, *entities))
+        .collect::<Vec<_>>();
+    for (colliders, _) in &stale {
+        pairs.remove(colliders);
+    }
+    stale
+}
+
+/// Records that both entities started or stopped touching, on those having a `Contacts` component.
+fn update_contacts(
+    contacts: &mut WriteStorage<Contacts>,
+    entity1: Entity,
+    entity2: Entity,
+    touching: bool,
+) {
+    for (owner, other) in &[(entity1, entity2), (entity2, entity1)] {
+        if let Some(contacts) = contacts.get_mut(*owner) {
+            if touching {
+                contacts.add(*other);
+            } else {
+                contacts.remove(*other);
+            }
+        }
+    }
 }
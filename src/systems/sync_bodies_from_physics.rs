@@ -1,11 +1,15 @@
 use crate::bodies::DynamicBody;
-use crate::colliders::Collider;
+use crate::events::{BodySleepEvent, BodyWakeEvent};
+use crate::handles::PhysicsHandles;
+use crate::interpolation::PhysicsInterpolation;
+use crate::joints::Joint;
 use crate::PhysicsWorld;
 use amethyst::core::{GlobalTransform, Transform};
-use amethyst::ecs::world::EntitiesRes;
-use amethyst::ecs::{Entities, Entity, Join, ReadExpect, ReadStorage, System, WriteStorage};
+use amethyst::ecs::{Entities, Join, Read, ReadExpect, System, Write, WriteStorage};
+use amethyst::shrev::EventChannel;
 use nalgebra::Vector3;
-use nphysics3d::object::{Body, BodyPart, ColliderHandle};
+use nphysics3d::math::Velocity;
+use nphysics3d::object::{Body, BodyPart};
 
 #[derive(Default)]
 pub struct SyncBodiesFromPhysicsSystem;
@@ -23,22 +27,33 @@ impl<'a> System<'a> for SyncBodiesFromPhysicsSystem {
         WriteStorage<'a, GlobalTransform>,
         WriteStorage<'a, DynamicBody>,
         WriteStorage<'a, Transform>,
+        Read<'a, PhysicsInterpolation>,
+        WriteStorage<'a, Joint>,
+        Read<'a, PhysicsHandles>,
+        Write<'a, EventChannel<BodySleepEvent>>,
+        Write<'a, EventChannel<BodyWakeEvent>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            _entities,
+            entities,
             physical_world,
             mut global_transforms,
             mut physics_bodies,
             mut local_transforms,
+            interpolation,
+            mut joints,
+            handles,
+            mut sleep_events,
+            mut wake_events,
         ) = data;
 
         trace!("Synchronizing bodies from physical world.");
 
         // Apply the updated values of the simulated world to our Components
         #[allow(unused_mut)]
-        for (mut global_transform, mut body, mut local_transform) in (
+        for (entity, mut global_transform, mut body, mut local_transform) in (
+            &entities,
             &mut global_transforms,
             &mut physics_bodies,
             (&mut local_transforms).maybe(),
@@ -47,6 +62,19 @@ impl<'a> System<'a> for SyncBodiesFromPhysicsSystem {
         {
             if let Some(updated_body_handle) = body.handle() {
                 if let Some(updated_body) = physical_world.rigid_body(updated_body_handle) {
+                    let sleeping = updated_body.is_dynamic() && !updated_body.is_active();
+                    if sleeping != body.sleeping {
+                        body.sleeping = sleeping;
+                        if sleeping {
+                            trace!("Body fell asleep: {:?}", updated_body.handle());
+                            body.velocity = Velocity::zero();
+                            sleep_events.single_write(BodySleepEvent { entity });
+                        } else {
+                            trace!("Body woke up: {:?}", updated_body.handle());
+                            wake_events.single_write(BodyWakeEvent { entity });
+                        }
+                    }
+
                     if !updated_body.is_active() || updated_body.is_static() {
                         trace!(
                             "Skipping synchronizing data from non-dynamic body: {:?}",
@@ -55,6 +83,16 @@ impl<'a> System<'a> for SyncBodiesFromPhysicsSystem {
                         continue;
                     }
 
+                    // The velocity of kinematic bodies was recorded by the `PhysicsStepperSystem`
+                    // before stopping them.
+                    if updated_body.is_kinematic() {
+                        trace!(
+                            "Skipping transform of kinematic body driven by its transform: {:?}",
+                            updated_body.handle()
+                        );
+                        continue;
+                    }
+
                     trace!(
                         "Synchronizing RigidBody from handle: {:?}",
                         updated_body.handle()
@@ -65,39 +103,29 @@ impl<'a> System<'a> for SyncBodiesFromPhysicsSystem {
                         updated_body.position()
                     );
 
-                    global_transform.0 = updated_body
-                        .position()
-                        .to_homogeneous()
-                        .prepend_nonuniform_scaling(
-                            &local_transform
-                                .as_ref()
-                                .map(|tr| *tr.scale())
-                                .unwrap_or_else(|| Vector3::new(1.0, 1.0, 1.0)),
-                        );
+                    let position = interpolation.position(
+                        updated_body_handle,
+                        updated_body.position(),
+                        updated_body.velocity(),
+                    );
+
+                    global_transform.0 = position.to_homogeneous().prepend_nonuniform_scaling(
+                        &local_transform
+                            .as_ref()
+                            .map(|tr| *tr.scale())
+                            .unwrap_or_else(|| Vector3::new(1.0, 1.0, 1.0)),
+                    );
 
                     if let Some(ref mut local_transform) = local_transform {
-                        *local_transform.isometry_mut() = *updated_body.position();
+                        *local_transform.isometry_mut() = position;
                     }
+                    body.synced_position = Some(position);
 
                     trace!(
                         "Synchronized RigidBody's updated velocity: {:?}",
                         updated_body.velocity()
                     );
                     body.velocity = *updated_body.velocity();
-
-                    trace!(
-                        "Synchronized RigidBody's updated inertia: {:?}",
-                        updated_body.inertia()
-                    );
-                    let inertia = updated_body.inertia();
-                    body.mass = inertia.linear;
-                    body.angular_mass = inertia.angular;
-
-                    trace!(
-                        "Synchronized RigidBody's updated center of mass: {:?}",
-                        updated_body.center_of_mass()
-                    );
-                    body.center_of_mass = updated_body.center_of_mass();
                 } else {
                     error!("Found body without pair in physics world!");
                 }
@@ -105,21 +133,22 @@ impl<'a> System<'a> for SyncBodiesFromPhysicsSystem {
                 error!("Found body without handle!");
             }
         }
-    }
-}
 
-// TODO: why is this here
-pub fn entity_from_handle(
-    entities: &EntitiesRes,
-    colliders: &ReadStorage<Collider>,
-    handle: ColliderHandle,
-) -> Option<Entity> {
-    (&*entities, colliders)
-        .join()
-        .find(|(_, c)| {
-            c.handle
-                .expect("Collider has no handle and wasn't removed.")
-                == handle
-        })
-        .map(|(e, _)| e)
+        trace!("Synchronizing joints from physical world.");
+
+        let joint_states = (&entities, &joints)
+            .join()
+            .filter_map(|(entity, joint)| {
+                let (handle1, handle2) = joint.body_handles(&handles)?;
+                let (position, velocity) = joint.measure(&physical_world, handle1, handle2)?;
+                Some((entity, position, velocity))
+            })
+            .collect::<Vec<_>>();
+        for (entity, position, velocity) in joint_states {
+            Joint::set_state_if_changed(&mut joints, entity, |state| {
+                state.position = position;
+                state.velocity = velocity;
+            });
+        }
+    }
 }
@@ -1,17 +1,24 @@
 use crate::bodies::DynamicBody;
+use crate::handles::PhysicsHandles;
 use crate::PhysicsWorld;
 use amethyst::core::GlobalTransform;
-use amethyst::ecs::storage::{ComponentEvent, GenericReadStorage, MaskedStorage};
+use amethyst::ecs::storage::{ComponentEvent, MaskedStorage};
 use amethyst::ecs::{
-    BitSet, Component, Entities, Join, ReadStorage, ReaderId, Resources, Storage, System,
-    SystemData, Tracked, WriteExpect, WriteStorage,
+    BitSet, Component, Entities, Entity, Join, ReadStorage, ReaderId, Resources, Storage, System,
+    SystemData, Tracked, Write, WriteExpect, WriteStorage,
 };
 use core::ops::Deref;
 use nalgebra::try_convert;
 use nalgebra::Isometry3;
-use nphysics3d::math::Isometry;
+use ncollide3d::bounding_volume::BoundingVolume;
+use ncollide3d::world::CollisionGroups;
+use nphysics3d::math::{Inertia, Isometry};
 
-use nphysics3d::object::{Body, RigidBodyDesc};
+use nphysics3d::object::{Body, BodyHandle, BodyStatus, RigidBodyDesc};
+
+/// Tolerance when comparing transforms to the poses written by the `SyncBodiesFromPhysicsSystem`,
+/// to absorb the loss of precision of the conversion to and from `GlobalTransform`.
+const SYNCED_POSITION_EPSILON: f32 = 1.0e-4;
 
 #[derive(Default)]
 pub struct SyncBodiesToPhysicsSystem {
@@ -31,10 +38,11 @@ impl<'a> System<'a> for SyncBodiesToPhysicsSystem {
         Entities<'a>,
         ReadStorage<'a, GlobalTransform>,
         WriteStorage<'a, DynamicBody>,
+        Write<'a, PhysicsHandles>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut physical_world, entities, transforms, mut physics_bodies) = data;
+        let (mut physical_world, entities, transforms, mut physics_bodies, mut handles) = data;
 
         let mut inserted_transforms = BitSet::new();
         let mut modified_transforms = BitSet::new();
@@ -49,8 +57,7 @@ impl<'a> System<'a> for SyncBodiesToPhysicsSystem {
             &mut inserted_transforms,
             &mut modified_transforms,
             &mut physical_world,
-            &entities,
-            &physics_bodies,
+            &mut handles,
         );
 
         // Get change flag events for physics bodies, removing deleted ones from the physics world.
@@ -61,8 +68,7 @@ impl<'a> System<'a> for SyncBodiesToPhysicsSystem {
             &mut inserted_physics_bodies,
             &mut modified_physics_bodies,
             &mut physical_world,
-            &entities,
-            &physics_bodies,
+            &mut handles,
         );
 
         // Update simulation world with the value of Components flagged as changed
@@ -89,64 +95,89 @@ impl<'a> System<'a> for SyncBodiesToPhysicsSystem {
                     }
                 }
 
-                /*body.handle = Some(physical_world.add_rigid_body(
-                    try_convert(transform.0).unwrap(),
-                    Inertia::new(body.mass, body.angular_mass),
-                    body.center_of_mass,
-                ));*/
-
                 let iso: Isometry3<f32> = try_convert(transform.0).unwrap();
 
-                body.handle = Some(
-                    RigidBodyDesc::new()
-                        .position(iso)
-                        //.gravity_enabled(false)
-                        .status(body.body_status)
-                        //.name("my rigid body".to_owned())
-                        .velocity(body.velocity)
-                        //.angular_inertia(3.0)
-                        .mass(1.2)
-                        //.local_inertia(Inertia::new(1.0, 3.0))
-                        .local_center_of_mass(body.center_of_mass)
-                        //.sleep_threshold(None)
-                        //.kinematic_translations(Vector2::new(true, false))
-                        //.kinematic_rotation(true)
-                        .user_data(entity)
-                        .build(&mut physical_world)
-                        .handle(),
-                );
-
-                trace!("Inserted rigid body to world with values: {:?}", body);
-
-                //let physical_body = physical_world.rigid_body_mut(body.handle.unwrap()).unwrap();
+                let mut rigid_body_desc = RigidBodyDesc::new()
+                    .position(iso)
+                    .gravity_enabled(body.uses_world_gravity())
+                    .status(body.body_status)
+                    //.name("my rigid body".to_owned())
+                    .velocity(body.locked_velocity(&body.velocity))
+                    .linear_damping(body.linear_damping)
+                    .angular_damping(body.angular_damping)
+                    .max_linear_velocity(body.max_linear_velocity.unwrap_or(std::f32::MAX))
+                    .max_angular_velocity(body.max_angular_velocity.unwrap_or(std::f32::MAX))
+                    .sleep_threshold(body.sleep_threshold)
+                    .kinematic_translations(body.locked_translations.as_vector())
+                    .kinematic_rotations(body.locked_rotations.as_vector())
+                    .user_data(entity);
 
-                //physical_body.set_velocity(body.velocity);
+                match body.validate_mass_properties() {
+                    Ok(()) => {
+                        rigid_body_desc = rigid_body_desc
+                            .local_inertia(Inertia::new(body.mass, body.angular_mass))
+                            .local_center_of_mass(body.center_of_mass);
+                    }
+                    Err(err) => error!(
+                        "Invalid mass properties for inserted body with id {}, using defaults: {}",
+                        id, err
+                    ),
+                }
 
-                // TODO
-                //physical_body.apply_force(&body.external_forces);
-                //body.external_forces = Force::<f32>::zero();
+                let rigid_body = rigid_body_desc.build(&mut physical_world);
+                body.apply_activation_request_to(rigid_body);
+                let handle = rigid_body.handle();
+                body.handle = Some(handle);
+                handles.insert_body(entity, handle);
 
-                trace!("Velocity and external forces applied, external forces reset to zero, for body with handle: {:?}", body.handle);
+                trace!("Inserted rigid body to world with values: {:?}", body);
             } else if modified_transforms.contains(id) || modified_physics_bodies.contains(id) {
                 trace!("Detected changed dynamics body with id {}", id);
-                if let Some(physical_body) = physical_world.rigid_body_mut(body.handle.unwrap()) {
-                    // if you changed the mass properties at all... too bad!
+                let handle = body.handle.unwrap();
+                let status_changed = physical_world
+                    .rigid_body(handle)
+                    .map_or(false, |physical_body| {
+                        physical_body.status() != body.body_status
+                    });
+                if status_changed {
+                    set_body_status(
+                        &mut physical_world,
+                        &handles,
+                        entity,
+                        handle,
+                        body.body_status,
+                    );
+                }
+
+                if let Some(physical_body) = physical_world.rigid_body_mut(handle) {
                     match try_convert(transform.0) {
                         Some(p) => {
                             let position: Isometry<f32> = p;
-                            trace!(
-                                "Updating rigid body in physics world with isometry: {}",
-                                position
-                            );
-                            physical_body.set_position(position);
-
-                            physical_body.set_velocity(body.velocity);
+                            if body.body_status == BodyStatus::Kinematic {
+                                // Kinematic bodies follow their transform through their velocity,
+                                // so that they push the dynamic bodies on their way.
+                                trace!(
+                                    "Moving kinematic rigid body in physics world to isometry: {}",
+                                    position
+                                );
+                                body.kinematic_target = Some(position);
+                            } else {
+                                body.kinematic_target = None;
+                                if moved_since_sync(&body, &position) {
+                                    trace!(
+                                        "Updating rigid body in physics world with isometry: {}",
+                                        position
+                                    );
+                                    physical_body.set_position(position);
+                                }
 
-                            // TODO
-                            //physical_body.apply_force(&body.external_forces);
-                            //body.external_forces = Force::<f32>::zero();
+                                physical_body.set_velocity(body.velocity);
+                            }
 
-                            physical_body.set_status(body.body_status);
+                            body.apply_mass_properties_to(physical_body);
+                            body.apply_motion_properties_to(physical_body);
+                            physical_body.enable_gravity(body.uses_world_gravity());
+                            body.apply_activation_request_to(physical_body);
                         }
                         None => error!(
                             "Failed to convert entity position from `Transform` to physics systems"
@@ -168,19 +199,72 @@ impl<'a> System<'a> for SyncBodiesToPhysicsSystem {
     }
 }
 
-fn iterate_events<T, D, S>(
+/// Changes the status of a rigid body, waking it and the bodies touching it up so that they react
+/// to the change, e.g. fall once a static floor is disabled.
+fn set_body_status(
+    world: &mut PhysicsWorld,
+    handles: &PhysicsHandles,
+    entity: Entity,
+    handle: BodyHandle,
+    status: BodyStatus,
+) {
+    trace!("Changing status of body {:?} to {:?}", handle, status);
+
+    let prediction = world.prediction();
+    let mut neighbours = Vec::new();
+    for collider in handles.colliders(entity) {
+        let collider = match world.collider(*collider) {
+            Some(collider) => collider,
+            None => continue,
+        };
+        let aabb = collider
+            .shape()
+            .aabb(collider.position())
+            .loosened(prediction);
+        neighbours.extend(
+            world
+                .collider_world()
+                .interferences_with_aabb(&aabb, &CollisionGroups::new())
+                .map(|other| other.body())
+                .filter(|other| *other != handle),
+        );
+    }
+
+    if let Some(physical_body) = world.rigid_body_mut(handle) {
+        physical_body.set_status(status);
+        physical_body.activate();
+    }
+    for neighbour in neighbours {
+        if let Some(neighbour) = world.rigid_body_mut(neighbour) {
+            neighbour.activate();
+        }
+    }
+}
+
+/// Whether the position differs from the one last written by the `SyncBodiesFromPhysicsSystem`.
+/// Interpolated poses are behind the simulation and must not be pushed back to it.
+fn moved_since_sync(body: &DynamicBody, position: &Isometry<f32>) -> bool {
+    match body.synced_position {
+        Some(synced) => {
+            (synced.translation.vector - position.translation.vector).norm()
+                > SYNCED_POSITION_EPSILON
+                || synced.rotation.angle_to(&position.rotation) > SYNCED_POSITION_EPSILON
+        }
+        None => true,
+    }
+}
+
+fn iterate_events<T, D>(
     tracked_storage: &Storage<T, D>,
     reader: &mut ReaderId<ComponentEvent>,
     inserted: &mut BitSet,
     modified: &mut BitSet,
     world: &mut PhysicsWorld,
-    entities: &Entities,
-    bodies: &S,
+    handles: &mut PhysicsHandles,
 ) where
     T: Component,
     T::Storage: Tracked,
     D: Deref<Target = MaskedStorage<T>>,
-    S: GenericReadStorage<Component = DynamicBody>,
 {
     let events = tracked_storage.channel().read(reader);
 
@@ -193,18 +277,14 @@ fn iterate_events<T, D, S>(
                 inserted.add(*id);
             }
             ComponentEvent::Removed(id) => {
-                match bodies.get(entities.entity(*id)) {
-                    Some(body) => {
-                        match body.handle() {
-                            Some(handle) => {
-                                trace!("Removing body with id: {}", id);
+                match handles.remove_body(*id) {
+                    Some(handle) => {
+                        trace!("Removing body with id: {}", id);
 
-                                world.remove_bodies(&[handle]);
-                            }
-                            None => {
-                                error!("Missing handle in body: {}", id);
-                            }
-                        };
+                        // Colliders attached to the body, including those of child entities, are
+                        // removed along with it.
+                        world.remove_bodies(&[handle]);
+                        handles.retain_colliders(|collider| world.collider(collider).is_some());
                     }
                     None => {
                         error!("Missing body with id: {}", id);
@@ -1,179 +1,483 @@
-use crate::bodies::DynamicBody;
-use crate::Collider;
+use crate::bodies::{DynamicBody, MassSource};
+use crate::colliders::{Collider, Colliders};
+use crate::handles::PhysicsHandles;
+use crate::materials::PhysicsMaterials;
 use crate::PhysicsWorld;
-use amethyst::core::Transform;
-use amethyst::ecs::storage::{ComponentEvent, GenericReadStorage, MaskedStorage};
+use amethyst::core::{GlobalTransform, Parent};
+use amethyst::ecs::storage::ComponentEvent;
+use amethyst::ecs::world::Index;
 use amethyst::ecs::{
-    BitSet, Component, Entities, Join, ReadStorage, ReaderId, Resources, Storage, System,
-    SystemData, Tracked, WriteExpect, WriteStorage,
+    BitSet, Entities, Entity, Join, Read, ReadStorage, ReaderId, Resources, System, SystemData,
+    Write, WriteExpect, WriteStorage,
 };
-use core::ops::Deref;
+use nalgebra::{try_convert, Isometry3};
 use nphysics::material::MaterialHandle;
-use nphysics::object::{BodyHandle, BodyPartHandle, ColliderDesc};
-
+use nphysics::object::{BodyPartHandle, ColliderDesc, ColliderHandle};
+use std::collections::HashMap;
+
+/// Largest difference between two poses of a child entity in its body, in distance and angle,
+/// that doesn't move its colliders.
+const POSE_EPSILON: f32 = 1.0e-5;
+
+/// Synchronizes `Collider` and `Colliders` components to the physics world.
+///
+/// Colliders are attached to the `DynamicBody` of their entity or, failing that, of its nearest
+/// ancestor through `Parent`. They are attached again when that ancestor changes, and follow the
+/// global transform of their entity relative to it. Colliders without any body are attached to the
+/// ground and follow the global transform of their entity.
 #[derive(Default, new)]
 pub struct SyncCollidersToPhysicsSystem {
     #[new(default)]
     colliders_reader_id: Option<ReaderId<ComponentEvent>>,
+    #[new(default)]
+    multi_colliders_reader_id: Option<ReaderId<ComponentEvent>>,
+    #[new(default)]
+    bodies_reader_id: Option<ReaderId<ComponentEvent>>,
+    #[new(default)]
+    transforms_reader_id: Option<ReaderId<ComponentEvent>>,
+    /// Handles of the colliders created for each component, to remove them once it is gone.
+    #[new(default)]
+    collider_handles: HashMap<Index, ColliderHandle>,
+    #[new(default)]
+    multi_collider_handles: HashMap<Index, Vec<ColliderHandle>>,
+    /// Components that couldn't be inserted yet, because the body of their owner or a transform
+    /// was missing. They are retried on every frame.
+    #[new(default)]
+    pending_colliders: BitSet,
+    #[new(default)]
+    pending_multi_colliders: BitSet,
+    /// Entity owning the body the colliders of each entity were attached to, to move them when the
+    /// entity gets another owner.
+    #[new(default)]
+    attached_owners: HashMap<Index, Option<Entity>>,
 }
 
 impl<'a> System<'a> for SyncCollidersToPhysicsSystem {
     type SystemData = (
         WriteExpect<'a, PhysicsWorld>,
         Entities<'a>,
-        ReadStorage<'a, Transform>,
-        ReadStorage<'a, DynamicBody>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadStorage<'a, Parent>,
+        WriteStorage<'a, DynamicBody>,
         WriteStorage<'a, Collider>,
+        WriteStorage<'a, Colliders>,
+        Write<'a, PhysicsHandles>,
+        Read<'a, PhysicsMaterials>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut physical_world, entities, transforms, rigid_bodies, mut colliders) = data;
-        // TODO: Check for inserted/removed rigid_bodies. parent sync https://nphysics.org/rustdoc/nphysics3d/world/struct.World.html?search=#method.add_collider
+        let (
+            mut physical_world,
+            entities,
+            transforms,
+            parents,
+            mut rigid_bodies,
+            mut colliders,
+            mut multi_colliders,
+            mut handles,
+            materials,
+        ) = data;
+
         let mut inserted_colliders = BitSet::new();
         let mut modified_colliders = BitSet::new();
+        let mut changed_multi_colliders = BitSet::new();
+        // Bodies whose mass has to be recomputed because their colliders changed.
+        let mut changed_bodies = BitSet::new();
 
-        iterate_events(
-            &colliders,
-            self.colliders_reader_id.as_mut().unwrap(),
-            &mut inserted_colliders,
-            &mut modified_colliders,
-            &mut physical_world,
-            &entities,
-            &colliders,
-        );
-
-        for (entity, mut collider, id, tr) in (
-            &entities,
-            &mut colliders,
-            &inserted_colliders | &modified_colliders,
-            &transforms,
-        )
-            .join()
+        trace!("Iterating collider storage events.");
+        for event in colliders
+            .channel()
+            .read(self.colliders_reader_id.as_mut().unwrap())
         {
-            if inserted_colliders.contains(id) {
-                trace!("Detected inserted collider with id {:?}", id);
-                // Just inserted. Remove old one and insert new.
-                if collider.handle.is_some()
-                    && physical_world.collider(collider.handle.unwrap()).is_some()
-                {
-                    physical_world.remove_colliders(&[collider.handle.unwrap()]);
+            match event {
+                ComponentEvent::Inserted(id) => {
+                    inserted_colliders.add(*id);
+                }
+                ComponentEvent::Modified(id) => {
+                    modified_colliders.add(*id);
+                }
+                ComponentEvent::Removed(id) => {
+                    trace!("Removing collider with id: {}", id);
+                    self.pending_colliders.remove(*id);
+                    if let Some(handle) = self.collider_handles.remove(id) {
+                        if let Some(body) =
+                            remove_collider(&mut physical_world, &mut handles, *id, handle)
+                        {
+                            changed_bodies.add(body.id());
+                        }
+                    }
                 }
+            }
+        }
 
-                let parent = if let Some(rb) = rigid_bodies.get(entity) {
-                    trace!("Attaching inserted collider to rigid body: {:?}", entity);
+        trace!("Iterating multi collider storage events.");
+        for event in multi_colliders
+            .channel()
+            .read(self.multi_colliders_reader_id.as_mut().unwrap())
+        {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    changed_multi_colliders.add(*id);
+                }
+                ComponentEvent::Removed(id) => {
+                    trace!("Removing multi collider with id: {}", id);
+                    self.pending_multi_colliders.remove(*id);
+                    for handle in self.multi_collider_handles.remove(id).unwrap_or_default() {
+                        if let Some(body) =
+                            remove_collider(&mut physical_world, &mut handles, *id, handle)
+                        {
+                            changed_bodies.add(body.id());
+                        }
+                    }
+                }
+            }
+        }
 
-                    rb.handle.expect(
-                        "You should normally have a body handle at this point. This is a bug.",
-                    )
-                } else {
-                    BodyHandle::ground()
-                };
-                let position = if parent.is_ground() {
-                    tr.isometry() * collider.offset_from_parent
-                } else {
-                    collider.offset_from_parent
-                };
+        // Only insertions are relevant here, modifications include the ones made by this system.
+        let inserted_bodies = rigid_bodies
+            .channel()
+            .read(self.bodies_reader_id.as_mut().unwrap())
+            .filter_map(|event| match event {
+                ComponentEvent::Inserted(id) => Some(*id),
+                _ => None,
+            })
+            .collect::<BitSet>();
+
+        // Colliders without a body are attached to the ground, and have to follow their transform.
+        let moved_transforms = transforms
+            .channel()
+            .read(self.transforms_reader_id.as_mut().unwrap())
+            .filter_map(|event| match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => Some(*id),
+                _ => None,
+            })
+            .collect::<BitSet>();
+
+        let owners = (&entities, colliders.mask() | multi_colliders.mask())
+            .join()
+            .map(|(entity, _)| (entity, body_owner(entity, &rigid_bodies, &parents)))
+            .collect::<HashMap<_, _>>();
+
+        // Colliders have to be recreated when their body was, which removed them from the physics
+        // world. This also happens when the body of an ancestor is removed. They are recreated as
+        // well when their entity is reparented to another body.
+        let is_lost = |entity: Entity, handle: ColliderHandle| {
+            handles.collider_entity(handle) != Some(entity)
+        };
+        let attached_owners = &self.attached_owners;
+        let needs_rebuild = |entity: Entity, handles: &mut dyn Iterator<Item = ColliderHandle>| {
+            let owner = owners[&entity];
+            owner.map_or(false, |owner| inserted_bodies.contains(owner.id()))
+                || attached_owners
+                    .get(&entity.id())
+                    .map_or(false, |attached| *attached != owner)
+                || handles.any(|handle| is_lost(entity, handle))
+        };
 
-                //trace!("Inserted collider to world with values: {:?}", collider);
-
-                let prediction = physical_world.prediction();
-                let angular_prediction = 0.09;
-
-                let parent_part_handle = physical_world
-                    .rigid_body(parent)
-                    .map(|body| body.part_handle())
-                    .unwrap_or_else(BodyPartHandle::ground);
-
-                collider.handle = Some(
-                    ColliderDesc::new(collider.shape.clone())
-                        .user_data(entity)
-                        .margin(collider.margin)
-                        .position(position)
-                        .material(MaterialHandle::new(collider.physics_material))
-                        .build_with_parent(parent_part_handle, &mut physical_world)
-                        .unwrap()
-                        .handle(),
-                );
+        let mut rebuilt_colliders = BitSet::new();
+        let mut updated_colliders = BitSet::new();
+        for (entity, collider) in (&entities, &colliders).join() {
+            let id = entity.id();
+            if inserted_colliders.contains(id)
+                || self.pending_colliders.contains(id)
+                || needs_rebuild(entity, &mut collider.handle.into_iter())
+            {
+                rebuilt_colliders.add(id);
+            } else if modified_colliders.contains(id) {
+                updated_colliders.add(id);
+            }
+        }
+        for (entity, multi_collider) in (&entities, &multi_colliders).join() {
+            if self.pending_multi_colliders.contains(entity.id())
+                || needs_rebuild(entity, &mut multi_collider.handles())
+            {
+                changed_multi_colliders.add(entity.id());
+            }
+        }
 
-                let collision_world = physical_world.collider_world_mut();
+        for (entity, _) in (&entities, &rebuilt_colliders).join() {
+            let owner = owners[&entity];
+            let collider = colliders.get_mut(entity).unwrap();
+            self.pending_colliders.remove(entity.id());
 
-                collision_world.as_collider_world_mut().set_query_type(
-                    collider.handle.unwrap(),
-                    collider.query_type.to_geometric_query_type(
-                        collider.margin,
-                        prediction,
-                        angular_prediction,
-                    ),
-                );
+            trace!("Detected inserted collider with id {:?}", entity.id());
+            // Just inserted. Remove old one and insert new.
+            if let Some(handle) = collider.handle.take() {
+                remove_collider(&mut physical_world, &mut handles, entity.id(), handle);
+            }
+            self.collider_handles.remove(&entity.id());
 
-                let collider_object = collision_world
-                    .collider_mut(collider.handle.unwrap())
-                    .unwrap();
+            let (parent, frame) =
+                match attachment(entity, owner, &physical_world, &rigid_bodies, &transforms) {
+                    Some(attachment) => attachment,
+                    None => {
+                        self.pending_colliders.add(entity.id());
+                        continue;
+                    }
+                };
 
-                let collider_handle = collider_object.handle();
+            if let Some(handle) = insert_collider(
+                &mut physical_world,
+                &mut handles,
+                &materials,
+                entity,
+                parent,
+                frame,
+                collider,
+            ) {
+                self.collider_handles.insert(entity.id(), handle);
+                self.attached_owners.insert(entity.id(), owner);
+                if let Some(owner) = owner {
+                    changed_bodies.add(owner.id());
+                }
+            }
+        }
+
+        for (entity, _) in (&entities, &updated_colliders).join() {
+            let owner = owners[&entity];
+            let collider = colliders.get_mut(entity).unwrap();
+            let handle = match collider.handle {
+                Some(handle) => handle,
+                None => continue,
+            };
+
+            trace!("Detected changed collider with id {:?}", entity.id());
+
+            let shape = match collider.shape.build() {
+                Ok(shape) => shape,
+                Err(err) => {
+                    error!(
+                        "Failed to build shape of changed collider {}: {}",
+                        entity.id(),
+                        err
+                    );
+                    continue;
+                }
+            };
+            collider.shape_handle = Some(shape.clone());
 
-                collision_world.set_collision_groups(collider_handle, collider.collision_group);
-            } else if modified_colliders.contains(id) || modified_colliders.contains(id) {
-                trace!("Detected changed collider with id {:?}", id);
+            let prediction = physical_world.prediction();
+            let angular_prediction = 0.09;
 
-                let prediction = physical_world.prediction();
-                let angular_prediction = 0.09;
+            let frame = attachment(entity, owner, &physical_world, &rigid_bodies, &transforms)
+                .map(|(_, frame)| frame);
 
-                let collision_world = physical_world.collider_world_mut();
-                let collider_handle = collision_world
-                    .collider(collider.handle.unwrap())
-                    .unwrap()
-                    .handle();
+            let collision_world = physical_world.collider_world_mut();
 
-                collision_world.set_collision_groups(collider_handle, collider.collision_group);
+            collision_world.set_collision_groups(handle, collider.collision_group);
+            collision_world
+                .as_collider_world_mut()
+                .set_shape(handle, shape);
+
+            if let Some(frame) = frame {
+                collider.frame_in_body = frame;
                 collision_world
-                    .as_collider_world_mut()
-                    .set_shape(collider_handle, collider.shape.clone());
+                    .collider_mut(handle)
+                    .unwrap()
+                    .set_position_wrt_body(frame * collider.offset_from_parent);
+            }
 
-                let collider_object = collision_world
-                    .collider_mut(collider.handle.unwrap())
-                    .unwrap();
+            collision_world.as_collider_world_mut().set_query_type(
+                handle,
+                collider.query_type.to_geometric_query_type(
+                    collider.margin,
+                    prediction,
+                    angular_prediction,
+                ),
+            );
+
+            collision_world
+                .collider_mut(handle)
+                .unwrap()
+                .set_material(MaterialHandle::new(collider.material(&materials)));
+
+            if let Some(owner) = owner {
+                changed_bodies.add(owner.id());
+            }
+        }
 
-                let parent = if let Some(rb) = rigid_bodies.get(entity) {
-                    trace!("Updating collider to rigid body: {:?}", entity);
+        // Joined on the storage, as the component may have been removed after being changed.
+        for (entity, multi_collider, _) in
+            (&entities, &mut multi_colliders, &changed_multi_colliders).join()
+        {
+            let owner = match owners.get(&entity) {
+                Some(owner) => *owner,
+                None => continue,
+            };
+            self.pending_multi_colliders.remove(entity.id());
+
+            trace!("Rebuilding multi collider with id {:?}", entity.id());
+            for collider in &mut multi_collider.colliders {
+                if let Some(handle) = collider.handle.take() {
+                    remove_collider(&mut physical_world, &mut handles, entity.id(), handle);
+                }
+            }
+            self.multi_collider_handles.remove(&entity.id());
+
+            let (parent, frame) =
+                match attachment(entity, owner, &physical_world, &rigid_bodies, &transforms) {
+                    Some(attachment) => attachment,
+                    None => {
+                        self.pending_multi_colliders.add(entity.id());
+                        continue;
+                    }
+                };
 
-                    rb.handle().expect(
-                        "You should normally have a body handle at this point. This is a bug.",
+            let collider_handles = multi_collider
+                .colliders
+                .iter_mut()
+                .filter_map(|collider| {
+                    insert_collider(
+                        &mut physical_world,
+                        &mut handles,
+                        &materials,
+                        entity,
+                        parent,
+                        frame,
+                        collider,
                     )
-                } else {
-                    trace!("Updating collider to ground.");
+                })
+                .collect::<Vec<_>>();
+            self.multi_collider_handles
+                .insert(entity.id(), collider_handles);
+            self.attached_owners.insert(entity.id(), owner);
+
+            if let Some(owner) = owner {
+                changed_bodies.add(owner.id());
+            }
+        }
 
-                    BodyHandle::ground()
-                };
+        trace!("Moving colliders attached to the body of an ancestor.");
+        let moved_children = owners
+            .iter()
+            .filter_map(|(entity, owner)| Some((*entity, (*owner)?)))
+            .filter(|(entity, owner)| {
+                let id = entity.id();
+                entity != owner
+                    && (moved_transforms.contains(id) || moved_transforms.contains(owner.id()))
+                    && !rebuilt_colliders.contains(id)
+                    && !updated_colliders.contains(id)
+                    && !changed_multi_colliders.contains(id)
+            })
+            .collect::<Vec<_>>();
+        for (entity, owner) in moved_children {
+            let frame = match frame_in_owner(entity, owner, &transforms) {
+                Some(frame) => frame,
+                None => continue,
+            };
+            let moved = |collider: &Collider| {
+                collider.handle.is_some() && !same_pose(&collider.frame_in_body, &frame)
+            };
+
+            // Only colliders that actually moved are written to, to keep the others unmodified.
+            let mut moved_any = false;
+            if colliders.get(entity).map_or(false, moved) {
+                let collider = colliders.get_mut(entity).unwrap();
+                move_in_body(&mut physical_world, collider, frame);
+                moved_any = true;
+            }
+            if multi_colliders.get(entity).map_or(false, |multi_collider| {
+                multi_collider.colliders.iter().any(moved)
+            }) {
+                let multi_collider = multi_colliders.get_mut(entity).unwrap();
+                for collider in &mut multi_collider.colliders {
+                    move_in_body(&mut physical_world, collider, frame);
+                }
+                moved_any = true;
+            }
+            if moved_any {
+                changed_bodies.add(owner.id());
+            }
+        }
 
-                let position = if parent.is_ground() {
-                    tr.isometry() * collider.offset_from_parent
-                } else {
-                    collider.offset_from_parent
+        trace!("Moving colliders attached to the ground.");
+        for (entity, transform, _) in (&entities, &transforms, &moved_transforms).join() {
+            let id = entity.id();
+            if owners.get(&entity) != Some(&None)
+                || rebuilt_colliders.contains(id)
+                || updated_colliders.contains(id)
+                || changed_multi_colliders.contains(id)
+            {
+                continue;
+            }
+            let isometry = match global_isometry(transform) {
+                Some(isometry) => isometry,
+                None => continue,
+            };
+
+            let entity_colliders = colliders.get(entity).into_iter().chain(
+                multi_colliders
+                    .get(entity)
+                    .into_iter()
+                    .flat_map(|multi_collider| multi_collider.colliders.iter()),
+            );
+            for collider in entity_colliders {
+                let handle = match collider.handle {
+                    Some(handle) => handle,
+                    None => continue,
                 };
+                if let Some(collider_object) =
+                    physical_world.collider_world_mut().collider_mut(handle)
+                {
+                    let position = isometry * collider.offset_from_parent;
+                    trace!("Moving static collider {:?} to {}", handle, position);
+                    collider_object.set_position(position);
+                }
+            }
+        }
 
-                collider_object.set_position(position);
+        // Recompute the mass properties of bodies whose colliders changed.
+        let bodies_to_update = (&entities, &rigid_bodies, &changed_bodies | &inserted_bodies)
+            .join()
+            .filter(|(_, body, _)| body.mass_source == MassSource::FromColliders)
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in bodies_to_update {
+            let body_colliders = owners
+                .iter()
+                .filter(|(_, owner)| **owner == Some(entity))
+                .flat_map(|(collider_entity, _)| {
+                    colliders.get(*collider_entity).into_iter().chain(
+                        multi_colliders
+                            .get(*collider_entity)
+                            .into_iter()
+                            .flat_map(|multi_collider| multi_collider.colliders.iter()),
+                    )
+                });
 
-                collision_world.as_collider_world_mut().set_query_type(
-                    collider.handle.unwrap(),
-                    collider.query_type.to_geometric_query_type(
-                        collider.margin,
-                        prediction,
-                        angular_prediction,
-                    ),
+            let body = rigid_bodies.get_mut(entity).unwrap();
+            if !body.set_mass_properties_from(body_colliders) {
+                warn!(
+                    "Body {:?} computes its mass from colliders but has none, keeping previous mass properties.",
+                    entity
                 );
+                continue;
+            }
+
+            trace!("Computed mass properties from colliders: {:?}", body);
 
-                // TODO: Material changes & more complex mats than BasicMaterial
-                /*collider_object
-                .user_data_mut()
-                .set_material(collider.physics_material.clone());*/
+            if let Some(rigid_body) = body
+                .handle()
+                .and_then(|handle| physical_world.rigid_body_mut(handle))
+            {
+                body.apply_mass_properties_to(rigid_body);
             }
         }
 
+        // Forget the owners of entities that don't have colliders anymore.
+        self.attached_owners
+            .retain(|id, _| colliders.mask().contains(*id) || multi_colliders.mask().contains(*id));
+
+        // Discard the modifications made by this system.
         colliders
             .channel()
             .read(&mut self.colliders_reader_id.as_mut().unwrap())
             .for_each(|_| ());
+        multi_colliders
+            .channel()
+            .read(&mut self.multi_colliders_reader_id.as_mut().unwrap())
+            .for_each(|_| ());
     }
 
     fn setup(&mut self, res: &mut Resources) {
@@ -181,52 +485,182 @@ impl<'a> System<'a> for SyncCollidersToPhysicsSystem {
 
         let mut collider_storage: WriteStorage<Collider> = SystemData::fetch(&res);
         self.colliders_reader_id = Some(collider_storage.register_reader());
+
+        let mut multi_collider_storage: WriteStorage<Colliders> = SystemData::fetch(&res);
+        self.multi_colliders_reader_id = Some(multi_collider_storage.register_reader());
+
+        let mut body_storage: WriteStorage<DynamicBody> = SystemData::fetch(&res);
+        self.bodies_reader_id = Some(body_storage.register_reader());
+
+        let mut transform_storage: WriteStorage<GlobalTransform> = SystemData::fetch(&res);
+        self.transforms_reader_id = Some(transform_storage.register_reader());
     }
 }
 
-fn iterate_events<T, D, S>(
-    tracked_storage: &Storage<T, D>,
-    reader: &mut ReaderId<ComponentEvent>,
-    inserted: &mut BitSet,
-    modified: &mut BitSet,
+/// Nearest entity owning a `DynamicBody`, starting with the entity itself and going up through its
+/// parents.
+fn body_owner(
+    entity: Entity,
+    bodies: &WriteStorage<DynamicBody>,
+    parents: &ReadStorage<Parent>,
+) -> Option<Entity> {
+    let mut current = entity;
+    loop {
+        if bodies.contains(current) {
+            return Some(current);
+        }
+        current = parents.get(current)?.entity;
+    }
+}
+
+/// Body part the colliders of the entity are attached to, and the pose of the entity in the local
+/// space of that part. `None` if the body or a transform is missing yet.
+fn attachment(
+    entity: Entity,
+    owner: Option<Entity>,
+    world: &PhysicsWorld,
+    bodies: &WriteStorage<DynamicBody>,
+    transforms: &ReadStorage<GlobalTransform>,
+) -> Option<(BodyPartHandle, Isometry3<f32>)> {
+    let owner = match owner {
+        Some(owner) => owner,
+        None => {
+            let isometry = global_isometry(transforms.get(entity)?)?;
+            return Some((BodyPartHandle::ground(), isometry));
+        }
+    };
+
+    let part = match bodies
+        .get(owner)
+        .and_then(|body| body.handle())
+        .and_then(|handle| world.rigid_body(handle))
+    {
+        Some(rigid_body) => rigid_body.part_handle(),
+        None => {
+            trace!(
+                "Body {:?} of collider {:?} isn't in the physics world yet, retrying later.",
+                owner,
+                entity
+            );
+            return None;
+        }
+    };
+
+    Some((part, frame_in_owner(entity, owner, transforms)?))
+}
+
+/// Pose of the entity in the local space of the entity owning its body, taken from their global
+/// transforms.
+fn frame_in_owner(
+    entity: Entity,
+    owner: Entity,
+    transforms: &ReadStorage<GlobalTransform>,
+) -> Option<Isometry3<f32>> {
+    if owner == entity {
+        return Some(Isometry3::identity());
+    }
+    let owner_isometry = global_isometry(transforms.get(owner)?)?;
+    Some(owner_isometry.inverse() * global_isometry(transforms.get(entity)?)?)
+}
+
+/// Whether two poses are the same, up to the precision lost when composing transforms.
+fn same_pose(a: &Isometry3<f32>, b: &Isometry3<f32>) -> bool {
+    (a.translation.vector - b.translation.vector).norm() <= POSE_EPSILON
+        && a.rotation.angle_to(&b.rotation) <= POSE_EPSILON
+}
+
+/// Moves an inserted collider to a new pose of its entity in the local space of its body.
+fn move_in_body(world: &mut PhysicsWorld, collider: &mut Collider, frame: Isometry3<f32>) {
+    collider.frame_in_body = frame;
+    let handle = match collider.handle {
+        Some(handle) => handle,
+        None => return,
+    };
+    if let Some(collider_object) = world.collider_world_mut().collider_mut(handle) {
+        trace!("Moving collider {:?} to {} in its body", handle, frame);
+        collider_object.set_position_wrt_body(frame * collider.offset_from_parent);
+    }
+}
+
+/// Inserts the collider into the physics world, attached to `parent` with its entity at `frame` in
+/// the local space of `parent`.
+fn insert_collider(
     world: &mut PhysicsWorld,
-    entities: &Entities,
-    colliders: &S,
-) where
-    T: Component,
-    T::Storage: Tracked,
-    D: Deref<Target = MaskedStorage<T>>,
-    S: GenericReadStorage<Component = Collider>,
-{
-    let events = tracked_storage.channel().read(reader);
-
-    for event in events {
-        match event {
-            ComponentEvent::Modified(id) => {
-                modified.add(*id);
-            }
-            ComponentEvent::Inserted(id) => {
-                inserted.add(*id);
-            }
-            ComponentEvent::Removed(id) => {
-                match colliders.get(entities.entity(*id)) {
-                    Some(collider) => {
-                        match collider.handle {
-                            Some(handle) => {
-                                trace!("Removing collider with id: {}", id);
-
-                                world.remove_colliders(&[handle]);
-                            }
-                            None => {
-                                error!("Missing handle in collider: {}", id);
-                            }
-                        };
-                    }
-                    None => {
-                        error!("Missing collider with id: {}", id);
-                    }
-                };
-            }
-        };
+    handles: &mut PhysicsHandles,
+    materials: &PhysicsMaterials,
+    entity: Entity,
+    parent: BodyPartHandle,
+    frame: Isometry3<f32>,
+    collider: &mut Collider,
+) -> Option<ColliderHandle> {
+    let shape = match collider.shape.build() {
+        Ok(shape) => shape,
+        Err(err) => {
+            error!(
+                "Failed to build shape of inserted collider {}: {}",
+                entity.id(),
+                err
+            );
+            return None;
+        }
+    };
+    collider.shape_handle = Some(shape.clone());
+    collider.frame_in_body = frame;
+
+    let prediction = world.prediction();
+    let angular_prediction = 0.09;
+
+    let handle = ColliderDesc::new(shape)
+        .user_data(entity)
+        .margin(collider.margin)
+        .position(frame * collider.offset_from_parent)
+        .material(MaterialHandle::new(collider.material(materials)))
+        .build_with_parent(parent, world)?
+        .handle();
+    collider.handle = Some(handle);
+    handles.insert_collider(entity, handle);
+
+    let collision_world = world.collider_world_mut();
+
+    collision_world.as_collider_world_mut().set_query_type(
+        handle,
+        collider.query_type.to_geometric_query_type(
+            collider.margin,
+            prediction,
+            angular_prediction,
+        ),
+    );
+
+    collision_world.set_collision_groups(handle, collider.collision_group);
+
+    Some(handle)
+}
+
+/// Removes a collider of the entity with the given index from the physics world, unless it was
+/// already removed along with its body. Returns the entity owning the body it was attached to.
+fn remove_collider(
+    world: &mut PhysicsWorld,
+    handles: &mut PhysicsHandles,
+    id: Index,
+    handle: ColliderHandle,
+) -> Option<Entity> {
+    // Handles of colliders removed along with their body may have been reused since.
+    if handles.collider_entity(handle).map(|entity| entity.id()) != Some(id) {
+        trace!("No collider left in physics world for id: {}", id);
+        return None;
+    }
+    handles.remove_collider(handle);
+
+    let body = world.collider(handle).map(|collider| collider.body());
+    world.remove_colliders(&[handle]);
+    body.and_then(|body| handles.body_entity(body))
+}
+
+/// Pose of the entity in world space, including the transforms of its parents.
+fn global_isometry(transform: &GlobalTransform) -> Option<Isometry3<f32>> {
+    let isometry = try_convert(transform.0);
+    if isometry.is_none() {
+        error!("Failed to convert entity position from `GlobalTransform` to physics systems");
     }
+    isometry
 }
@@ -0,0 +1,141 @@
+use crate::bodies::DynamicBody;
+use crate::gravity_fields::{
+    FieldBody, FieldState, GravityField, GravityFieldsForceGenerator, GravityFieldsState,
+};
+use crate::PhysicsWorld;
+use amethyst::core::GlobalTransform;
+use amethyst::ecs::storage::ComponentEvent;
+use amethyst::ecs::world::Index;
+use amethyst::ecs::{
+    Entities, Join, ReadStorage, ReaderId, Resources, System, SystemData, WriteExpect, WriteStorage,
+};
+use nalgebra::{try_convert, Isometry3};
+use ncollide::shape::ShapeHandle;
+use nphysics::force_generator::ForceGeneratorHandle;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Pushes the `GravityField` components to the force generator applying them in the physics
+/// world, which is added on the first frame there is a field.
+#[derive(Default)]
+pub struct SyncGravityFieldsToPhysicsSystem {
+    fields_reader_id: Option<ReaderId<ComponentEvent>>,
+    /// Shapes built from the fields, rebuilt when the field is modified.
+    shapes: HashMap<Index, Option<ShapeHandle<f32>>>,
+    state: Arc<Mutex<GravityFieldsState>>,
+    force_generator: Option<ForceGeneratorHandle>,
+}
+
+impl SyncGravityFieldsToPhysicsSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for SyncGravityFieldsToPhysicsSystem {
+    type SystemData = (
+        WriteExpect<'a, PhysicsWorld>,
+        Entities<'a>,
+        ReadStorage<'a, GravityField>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadStorage<'a, DynamicBody>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut physical_world, entities, fields, transforms, bodies) = data;
+
+        trace!("Iterating gravity field storage events.");
+        for event in fields
+            .channel()
+            .read(self.fields_reader_id.as_mut().unwrap())
+        {
+            match event {
+                ComponentEvent::Inserted(id)
+                | ComponentEvent::Modified(id)
+                | ComponentEvent::Removed(id) => {
+                    self.shapes.remove(id);
+                }
+            }
+        }
+
+        let mut field_states = Vec::new();
+        for (entity, field, transform) in (&entities, &fields, &transforms).join() {
+            let position: Isometry3<f32> = match try_convert(transform.0) {
+                Some(position) => position,
+                None => {
+                    error!("Failed to convert gravity field position from `GlobalTransform` to physics systems");
+                    continue;
+                }
+            };
+
+            let shape = self
+                .shapes
+                .entry(entity.id())
+                .or_insert_with(|| match &field.shape {
+                    Some(shape) => match shape.build() {
+                        Ok(shape) => Some(shape),
+                        Err(err) => {
+                            error!(
+                                "Failed to build shape of gravity field {:?}: {}",
+                                entity, err
+                            );
+                            None
+                        }
+                    },
+                    None => None,
+                });
+            // A field whose shape failed to build doesn't apply anywhere.
+            if field.shape.is_some() && shape.is_none() {
+                continue;
+            }
+
+            field_states.push(FieldState {
+                kind: field.kind,
+                shape: shape.clone(),
+                position,
+                priority: field.priority,
+                overrides_gravity: field.overrides_gravity,
+            });
+        }
+
+        let world_gravity = *physical_world.gravity();
+        let field_bodies = bodies
+            .join()
+            .filter_map(|body| {
+                body.handle().map(|handle| FieldBody {
+                    handle,
+                    gravity: body.gravity(&world_gravity),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        match self.state.lock() {
+            Ok(mut state) => {
+                state.fields = field_states;
+                state.bodies = field_bodies;
+                if state.fields.is_empty() {
+                    return;
+                }
+            }
+            Err(_) => {
+                error!("Gravity fields state poisoned, fields won't be updated.");
+                return;
+            }
+        }
+
+        if self.force_generator.is_none() {
+            trace!("Adding gravity fields force generator to the physics world.");
+            self.force_generator = Some(
+                physical_world
+                    .add_force_generator(GravityFieldsForceGenerator::new(self.state.clone())),
+            );
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+
+        let mut field_storage: WriteStorage<GravityField> = SystemData::fetch(&res);
+        self.fields_reader_id = Some(field_storage.register_reader());
+    }
+}
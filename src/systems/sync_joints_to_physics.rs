@@ -0,0 +1,146 @@
+use crate::handles::PhysicsHandles;
+use crate::joints::{Joint, JointType};
+use crate::PhysicsWorld;
+use amethyst::ecs::storage::ComponentEvent;
+use amethyst::ecs::world::Index;
+use amethyst::ecs::{
+    Entities, Entity, Join, ReadStorage, ReaderId, Resources, System, SystemData, Write,
+    WriteExpect, WriteStorage,
+};
+use nalgebra::Isometry3;
+use nphysics::object::{BodyHandle, BodyPartHandle};
+use std::collections::HashMap;
+
+/// Parts of a `Joint` its constraint was created from. The constraint is recreated when any of
+/// them changes, other changes like motors and limits are applied to the existing constraint.
+#[derive(PartialEq)]
+struct BuiltJoint {
+    bodies: (BodyHandle, BodyHandle),
+    joint_type: JointType,
+    anchor1: Isometry3<f32>,
+    anchor2: Isometry3<f32>,
+}
+
+#[derive(Default)]
+pub struct SyncJointsToPhysicsSystem {
+    joints_reader_id: Option<ReaderId<ComponentEvent>>,
+    built_joints: HashMap<Index, BuiltJoint>,
+}
+
+impl SyncJointsToPhysicsSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for SyncJointsToPhysicsSystem {
+    type SystemData = (
+        WriteExpect<'a, PhysicsWorld>,
+        Entities<'a>,
+        ReadStorage<'a, Joint>,
+        Write<'a, PhysicsHandles>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut physical_world, entities, joints, mut handles) = data;
+
+        trace!("Iterating joint storage events.");
+        for event in joints
+            .channel()
+            .read(self.joints_reader_id.as_mut().unwrap())
+        {
+            if let ComponentEvent::Removed(id) = event {
+                trace!("Removing joint with id: {}", id);
+                self.built_joints.remove(id);
+                if let Some(handle) = handles.remove_joint(*id) {
+                    physical_world.remove_constraint(handle);
+                }
+            }
+        }
+
+        let body_handle = |entity: Entity| {
+            if entities.is_alive(entity) {
+                handles.body(entity)
+            } else {
+                None
+            }
+        };
+
+        // Bodies may have been inserted or removed since last frame, in which case the joint
+        // constraint has to be created, recreated or removed too, without the joint being
+        // modified. Joints are also modified whenever their state is read back from the physics
+        // world, so they are compared with what their constraint was built from instead of relying
+        // on modification events.
+        let mut outdated_joints = Vec::new();
+        for (entity, joint) in (&entities, &joints).join() {
+            if joint.broken {
+                if self.built_joints.contains_key(&entity.id()) {
+                    outdated_joints.push((entity, None));
+                }
+                continue;
+            }
+
+            let built_joint = match (
+                body_handle(joint.body1),
+                joint.body2.map_or(Some(BodyHandle::ground()), &body_handle),
+            ) {
+                (Some(handle1), Some(handle2)) => Some(BuiltJoint {
+                    bodies: (handle1, handle2),
+                    joint_type: joint.joint_type,
+                    anchor1: joint.anchor1,
+                    anchor2: joint.anchor2,
+                }),
+                _ => None,
+            };
+
+            if self.built_joints.get(&entity.id()) != built_joint.as_ref() {
+                outdated_joints.push((entity, built_joint));
+            }
+        }
+
+        for (entity, built_joint) in outdated_joints {
+            if let Some(handle) = handles.remove_joint(entity.id()) {
+                trace!("Removing outdated joint constraint: {:?}", handle);
+                physical_world.remove_constraint(handle);
+            }
+            self.built_joints.remove(&entity.id());
+
+            let built_joint = match built_joint {
+                Some(built_joint) => built_joint,
+                None => {
+                    trace!("Waiting for the bodies of joint {:?}", entity);
+                    continue;
+                }
+            };
+            let (handle1, handle2) = built_joint.bodies;
+
+            let part_handle = |handle: BodyHandle| {
+                physical_world
+                    .rigid_body(handle)
+                    .map(|body| body.part_handle())
+                    .unwrap_or_else(BodyPartHandle::ground)
+            };
+            let (part1, part2) = (part_handle(handle1), part_handle(handle2));
+
+            let joint = joints.get(entity).unwrap();
+            let handle = joint.add_to_world(&mut physical_world, part1, part2);
+            trace!("Inserted joint constraint {:?} for {:?}", handle, joint);
+
+            handles.insert_joint(entity, handle);
+            self.built_joints.insert(entity.id(), built_joint);
+        }
+
+        for (entity, joint) in (&entities, &joints).join() {
+            if let Some(handle) = handles.joint(entity) {
+                joint.apply_limits(&mut physical_world, handle);
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+
+        let mut joint_storage: WriteStorage<Joint> = SystemData::fetch(&res);
+        self.joints_reader_id = Some(joint_storage.register_reader());
+    }
+}
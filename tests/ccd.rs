@@ -0,0 +1,122 @@
+//! A small ball is fired at a thin wall, fast enough to cross it several times over during a single
+//! physics step. With `ccd_enabled`, it must bounce off the wall instead of going through it.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Join;
+use amethyst::core::math::Vector3;
+use amethyst::core::{GlobalTransform, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point, Velocity};
+use nphysics_ecs_dumb::*;
+
+const BALL_RADIUS: f32 = 0.05;
+/// Distance covered in a step of the default `TimeStep::Fixed(1/120)`: 2.75 units, for a wall
+/// 0.02 thick. Without CCD, the ball is 1.69 units in front of the wall after the third step, and
+/// 0.94 units behind it after the fourth, so it never touches it.
+const BALL_SPEED: f32 = 330.0;
+const WALL_X: f32 = 10.0;
+const WALL_HALF_THICKNESS: f32 = 0.01;
+const FRAMES: u32 = 120;
+
+struct CcdState {
+    ccd_enabled: bool,
+    frames: u32,
+    /// Farthest position of the ball along `x`.
+    max_x: f32,
+}
+
+impl CcdState {
+    fn new(ccd_enabled: bool) -> Self {
+        CcdState {
+            ccd_enabled,
+            frames: 0,
+            max_x: 0.0,
+        }
+    }
+}
+
+impl SimpleState for CcdState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        data.world.add_resource(Gravity::zeros());
+
+        let mut ball = DynamicBody::new_rigidbody_with_velocity(
+            Velocity::linear(BALL_SPEED, 0.0, 0.0),
+            0.1,
+            AngularInertia::identity(),
+            Point::origin(),
+        );
+        ball.ccd_enabled = self.ccd_enabled;
+        ball.ccd_motion_threshold = BALL_RADIUS;
+
+        data.world
+            .create_entity()
+            .with(Transform::default())
+            .with(GlobalTransform::default())
+            .with(ball)
+            .with(
+                ColliderBuilder::from(ShapeDesc::Ball {
+                    radius: BALL_RADIUS,
+                })
+                .build()
+                .unwrap(),
+            )
+            .build();
+
+        // Static wall, a collider without body.
+        data.world
+            .create_entity()
+            .with(Transform::from(Vector3::new(WALL_X, 0.0, 0.0)))
+            .with(GlobalTransform::default())
+            .with(
+                ColliderBuilder::from(ShapeDesc::Cuboid {
+                    half_extents: Vector3::new(WALL_HALF_THICKNESS, 5.0, 5.0),
+                })
+                .build()
+                .unwrap(),
+            )
+            .build();
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+
+        let transforms = data.world.read_storage::<Transform>();
+        let bodies = data.world.read_storage::<DynamicBody>();
+        for (transform, _) in (&transforms, &bodies).join() {
+            let x = transform.translation().x;
+            self.max_x = self.max_x.max(x);
+            if self.ccd_enabled {
+                assert!(
+                    x < WALL_X,
+                    "Ball tunneled through the wall on frame {}: x = {}",
+                    self.frames,
+                    x
+                );
+            }
+        }
+
+        if self.frames < FRAMES {
+            return Trans::None;
+        }
+
+        if !self.ccd_enabled {
+            assert!(
+                self.max_x > WALL_X,
+                "Ball didn't tunnel through the wall without CCD, the test can't catch tunneling"
+            );
+        }
+        Trans::Quit
+    }
+}
+
+#[test]
+fn ccd_stops_fast_ball_at_thin_wall() {
+    common::run(CcdState::new(true), common::physics_game_data());
+}
+
+#[test]
+fn fast_ball_tunnels_without_ccd() {
+    common::run(CcdState::new(false), common::physics_game_data());
+}
@@ -0,0 +1,27 @@
+//! Helpers running headless applications with the physics bundle, for the integration tests.
+
+#![allow(dead_code)]
+
+use amethyst::core::TransformBundle;
+use amethyst::{Application, GameDataBuilder, SimpleState};
+use nphysics_ecs_dumb::PhysicsBundle;
+
+/// Game data with the transform and physics bundles, the physics running after the transforms.
+pub fn physics_game_data() -> GameDataBuilder<'static, 'static> {
+    GameDataBuilder::default()
+        .with_bundle(TransformBundle::new())
+        .expect("Failed to add the transform bundle")
+        .with_bundle(PhysicsBundle::new().with_dep(&["transform_system"]))
+        .expect("Failed to add the physics bundle")
+}
+
+/// Runs the application until the state quits. Assertions failing in the state fail the test.
+pub fn run<S>(state: S, game_data: GameDataBuilder<'static, 'static>)
+where
+    S: SimpleState<'static, 'static> + 'static,
+{
+    let assets_dir = format!("{}/examples/assets", env!("CARGO_MANIFEST_DIR"));
+    Application::new(assets_dir, state, game_data)
+        .expect("Failed to create the application")
+        .run();
+}
@@ -0,0 +1,154 @@
+//! Contact events report the impulses the solver applied at the contact, not the other forces
+//! acting on the bodies.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::Vector3;
+use amethyst::core::shrev::{EventChannel, ReaderId};
+use amethyst::core::{GlobalTransform, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point, Velocity};
+use nphysics_ecs_dumb::*;
+
+const MAX_FRAMES: u32 = 60;
+const BALL_RADIUS: f32 = 0.5;
+const BALL_MASS: f32 = 1.0;
+/// Speed of the ball hitting the wall, which the contact stops.
+const HIT_SPEED: f32 = 2.0;
+/// Force pushing the other ball away from the wall.
+const PUSH_FORCE: f32 = 100.0;
+
+#[derive(Default)]
+struct ContactImpulseState {
+    frames: u32,
+    reader: Option<ReaderId<EntityContactEvent>>,
+    hitting: Option<Entity>,
+    pushed: Option<Entity>,
+    hitting_impulse: Option<f32>,
+    pushed_impulse: Option<f32>,
+}
+
+fn ball(position: f32, velocity: f32) -> (Transform, DynamicBody, Collider) {
+    let body = DynamicBody::new_rigidbody_with_velocity(
+        Velocity::linear(velocity, 0.0, 0.0),
+        BALL_MASS,
+        AngularInertia::identity(),
+        Point::origin(),
+    );
+    let collider = ColliderBuilder::from(ShapeDesc::Ball {
+        radius: BALL_RADIUS,
+    })
+    .build()
+    .unwrap();
+    (
+        Transform::from(Vector3::new(position, 0.0, 0.0)),
+        body,
+        collider,
+    )
+}
+
+impl SimpleState for ContactImpulseState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let world = data.world;
+        world.add_resource(Gravity::zeros());
+        self.reader = Some(
+            world
+                .write_resource::<EventChannel<EntityContactEvent>>()
+                .register_reader(),
+        );
+
+        // Static wall, its faces at `x = ±0.5`.
+        world
+            .create_entity()
+            .with(Transform::default())
+            .with(GlobalTransform::default())
+            .with(
+                ColliderBuilder::from(ShapeDesc::Cuboid {
+                    half_extents: Vector3::new(0.5, 5.0, 5.0),
+                })
+                .build()
+                .unwrap(),
+            )
+            .build();
+
+        // Both balls start touching the wall, one moving into it, the other pushed away from it.
+        let (transform, body, collider) = ball(-1.0, HIT_SPEED);
+        self.hitting = Some(
+            world
+                .create_entity()
+                .with(transform)
+                .with(GlobalTransform::default())
+                .with(body)
+                .with(collider)
+                .build(),
+        );
+
+        let (transform, mut body, collider) = ball(1.0, 0.0);
+        body.apply_force(Vector3::new(PUSH_FORCE, 0.0, 0.0));
+        self.pushed = Some(
+            world
+                .create_entity()
+                .with(transform)
+                .with(GlobalTransform::default())
+                .with(body)
+                .with(collider)
+                .build(),
+        );
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+
+        let events = data
+            .world
+            .read_resource::<EventChannel<EntityContactEvent>>();
+        for event in events.read(self.reader.as_mut().unwrap()) {
+            if event.state != ContactState::Started {
+                continue;
+            }
+            let involves = |entity: Option<Entity>| {
+                Some(event.entity1) == entity || Some(event.entity2) == entity
+            };
+            if involves(self.hitting) && self.hitting_impulse.is_none() {
+                self.hitting_impulse = Some(event.impulse);
+            }
+            if involves(self.pushed) && self.pushed_impulse.is_none() {
+                self.pushed_impulse = Some(event.impulse);
+            }
+        }
+
+        let (hitting_impulse, pushed_impulse) = match (self.hitting_impulse, self.pushed_impulse) {
+            (Some(hitting), Some(pushed)) => (hitting, pushed),
+            _ => {
+                assert!(
+                    self.frames < MAX_FRAMES,
+                    "Contacts with the wall didn't start after {} frames",
+                    MAX_FRAMES
+                );
+                return Trans::None;
+            }
+        };
+
+        let expected = BALL_MASS * HIT_SPEED;
+        assert!(
+            (hitting_impulse - expected).abs() < 0.1 * expected,
+            "Impulse stopping the ball is {}, expected {}",
+            hitting_impulse,
+            expected
+        );
+        assert!(
+            pushed_impulse.abs() < 1.0e-3,
+            "The wall doesn't hold the pushed ball back, yet its contact reports an impulse of {}",
+            pushed_impulse
+        );
+
+        Trans::Quit
+    }
+}
+
+#[test]
+fn contact_impulses_come_from_the_solver() {
+    common::run(ContactImpulseState::default(), common::physics_game_data());
+}
@@ -0,0 +1,159 @@
+//! Colliders of child entities follow their entity when it is reparented to another body, or
+//! moved relative to its body.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::Vector3;
+use amethyst::core::{GlobalTransform, Parent, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point};
+use nphysics_ecs_dumb::*;
+
+/// Frames allowed for each change to reach the physics world.
+const MAX_FRAMES: u32 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    /// Waiting for the child collider to be attached to the first body.
+    Inserted,
+    /// The child was reparented to the second body.
+    Reparented,
+    /// The child was moved relative to the second body.
+    Moved,
+}
+
+struct HierarchyState {
+    phase: Phase,
+    phase_frames: u32,
+    first: Option<Entity>,
+    second: Option<Entity>,
+    child: Option<Entity>,
+}
+
+fn body(world: &mut amethyst::ecs::World, x: f32) -> Entity {
+    world
+        .create_entity()
+        .with(Transform::from(Vector3::new(x, 0.0, 0.0)))
+        .with(GlobalTransform::default())
+        .with(DynamicBody::new_rigidbody(
+            1.0,
+            AngularInertia::identity(),
+            Point::origin(),
+        ))
+        .build()
+}
+
+impl HierarchyState {
+    /// Whether the child collider is attached to the body of `owner`, with its entity at `local`
+    /// in the space of that body.
+    fn collider_attached(
+        &self,
+        data: &StateData<'_, GameData<'_, '_>>,
+        owner: Entity,
+        local: Vector3<f32>,
+    ) -> bool {
+        let physics_world = data.world.read_resource::<PhysicsWorld>();
+        let handles = data.world.read_resource::<PhysicsHandles>();
+        let handle = match handles.colliders(self.child.unwrap()).first() {
+            Some(handle) => *handle,
+            None => return false,
+        };
+        let (collider, body) = match (physics_world.collider(handle), handles.body(owner)) {
+            (Some(collider), Some(body)) => (collider, body),
+            _ => return false,
+        };
+        let body_position = physics_world.rigid_body(body).unwrap().position();
+        let expected = body_position * Point::from(local);
+        collider.body() == body
+            && (collider.position().translation.vector - expected.coords).norm() < 1.0e-3
+    }
+}
+
+impl SimpleState for HierarchyState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let world = data.world;
+        world.add_resource(Gravity::zeros());
+
+        let first = body(world, 0.0);
+        let second = body(world, 10.0);
+        let child = world
+            .create_entity()
+            .with(Transform::from(Vector3::new(1.0, 0.0, 0.0)))
+            .with(GlobalTransform::default())
+            .with(Parent { entity: first })
+            .with(
+                ColliderBuilder::from(ShapeDesc::Ball { radius: 0.5 })
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+
+        self.first = Some(first);
+        self.second = Some(second);
+        self.child = Some(child);
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.phase_frames += 1;
+        assert!(
+            self.phase_frames <= MAX_FRAMES,
+            "Child collider not attached as expected after {} frames, phase {:?}",
+            MAX_FRAMES,
+            self.phase
+        );
+
+        let child = self.child.unwrap();
+        match self.phase {
+            Phase::Inserted => {
+                if !self.collider_attached(data, self.first.unwrap(), Vector3::new(1.0, 0.0, 0.0)) {
+                    return Trans::None;
+                }
+                data.world
+                    .write_storage::<Parent>()
+                    .insert(
+                        child,
+                        Parent {
+                            entity: self.second.unwrap(),
+                        },
+                    )
+                    .unwrap();
+                self.phase = Phase::Reparented;
+            }
+            Phase::Reparented => {
+                if !self.collider_attached(data, self.second.unwrap(), Vector3::new(1.0, 0.0, 0.0))
+                {
+                    return Trans::None;
+                }
+                data.world
+                    .write_storage::<Transform>()
+                    .get_mut(child)
+                    .unwrap()
+                    .set_xyz(0.0, 2.0, 0.0);
+                self.phase = Phase::Moved;
+            }
+            Phase::Moved => {
+                if !self.collider_attached(data, self.second.unwrap(), Vector3::new(0.0, 2.0, 0.0))
+                {
+                    return Trans::None;
+                }
+                return Trans::Quit;
+            }
+        }
+        self.phase_frames = 0;
+        Trans::None
+    }
+}
+
+#[test]
+fn child_colliders_follow_reparenting_and_moves() {
+    let state = HierarchyState {
+        phase: Phase::Inserted,
+        phase_frames: 0,
+        first: None,
+        second: None,
+        child: None,
+    };
+    common::run(state, common::physics_game_data());
+}
@@ -0,0 +1,139 @@
+//! Breakable joints break under the load they carry, not under impulses their bodies receive
+//! along the directions the joint leaves free.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::{Isometry3, Vector3};
+use amethyst::core::{GlobalTransform, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point, Velocity};
+use nphysics_ecs_dumb::*;
+
+const FRAMES: u32 = 60;
+/// Below the impulse needed to hold the hanging body against gravity during a step, and far below
+/// the impulse of the ball hitting the slider.
+const MAX_LINEAR_IMPULSE: f32 = 0.4;
+const HANGING_MASS: f32 = 10.0;
+
+struct JointsState {
+    frames: u32,
+    loaded_joint: Option<Entity>,
+    unloaded_joint: Option<Entity>,
+    slider: Option<Entity>,
+}
+
+impl SimpleState for JointsState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let world = data.world;
+
+        // Body hanging from the ground by a ball joint, which has to hold its weight.
+        let hanging = world
+            .create_entity()
+            .with(Transform::from(Vector3::new(5.0, -1.0, 0.0)))
+            .with(GlobalTransform::default())
+            .with(DynamicBody::new_rigidbody(
+                HANGING_MASS,
+                AngularInertia::identity(),
+                Point::origin(),
+            ))
+            .build();
+        let mut loaded_joint = Joint::new(
+            JointType::Ball,
+            hanging,
+            None,
+            Isometry3::translation(0.0, 1.0, 0.0),
+            Isometry3::translation(5.0, 0.0, 0.0),
+        );
+        loaded_joint.max_linear_impulse = Some(MAX_LINEAR_IMPULSE);
+        self.loaded_joint = Some(world.create_entity().with(loaded_joint).build());
+
+        // Weightless slider on a prismatic joint along `x`, hit along that axis by a ball. The
+        // joint leaves that translation free, so it carries no load.
+        let mut slider_body =
+            DynamicBody::new_rigidbody(1.0, AngularInertia::identity(), Point::origin());
+        slider_body.gravity_enabled = false;
+        let slider = world
+            .create_entity()
+            .with(Transform::default())
+            .with(GlobalTransform::default())
+            .with(slider_body)
+            .with(
+                ColliderBuilder::from(ShapeDesc::Cuboid {
+                    half_extents: Vector3::new(0.5, 0.5, 0.5),
+                })
+                .build()
+                .unwrap(),
+            )
+            .build();
+        let mut unloaded_joint = Joint::new(
+            JointType::Prismatic,
+            slider,
+            None,
+            Isometry3::identity(),
+            Isometry3::identity(),
+        );
+        unloaded_joint.max_linear_impulse = Some(MAX_LINEAR_IMPULSE);
+        self.unloaded_joint = Some(world.create_entity().with(unloaded_joint).build());
+        self.slider = Some(slider);
+
+        let mut ball_body = DynamicBody::new_rigidbody_with_velocity(
+            Velocity::linear(10.0, 0.0, 0.0),
+            1.0,
+            AngularInertia::identity(),
+            Point::origin(),
+        );
+        ball_body.gravity_enabled = false;
+        world
+            .create_entity()
+            .with(Transform::from(Vector3::new(-3.0, 0.0, 0.0)))
+            .with(GlobalTransform::default())
+            .with(ball_body)
+            .with(
+                ColliderBuilder::from(ShapeDesc::Ball { radius: 0.25 })
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+        if self.frames < FRAMES {
+            return Trans::None;
+        }
+
+        let joints = data.world.read_storage::<Joint>();
+        let transforms = data.world.read_storage::<Transform>();
+
+        let slider = transforms.get(self.slider.unwrap()).unwrap();
+        assert!(
+            slider.translation().x > 0.1,
+            "The ball didn't push the slider: {}",
+            slider.translation()
+        );
+
+        assert!(
+            joints.get(self.loaded_joint.unwrap()).unwrap().broken,
+            "The joint holding the hanging body didn't break"
+        );
+        assert!(
+            !joints.get(self.unloaded_joint.unwrap()).unwrap().broken,
+            "The joint of the slider broke when the ball hit it"
+        );
+
+        Trans::Quit
+    }
+}
+
+#[test]
+fn joints_break_under_their_own_load_only() {
+    let state = JointsState {
+        frames: 0,
+        loaded_joint: None,
+        unloaded_joint: None,
+        slider: None,
+    };
+    common::run(state, common::physics_game_data());
+}
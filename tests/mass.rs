@@ -0,0 +1,155 @@
+//! Bodies computing their mass from capsule colliders, on their own or in a compound shape.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::{Isometry3, Matrix3, Vector3};
+use amethyst::core::{GlobalTransform, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::*;
+use std::f32::consts::PI;
+
+const MAX_FRAMES: u32 = 60;
+const HALF_HEIGHT: f32 = 1.0;
+const RADIUS: f32 = 0.5;
+const DENSITY: f32 = 2.0;
+/// Offset of each capsule of the compound shape along `x`.
+const OFFSET: f32 = 1.5;
+
+#[derive(Default)]
+struct CapsuleMassState {
+    frames: u32,
+    capsule: Option<Entity>,
+    compound: Option<Entity>,
+}
+
+fn capsule() -> ShapeDesc {
+    ShapeDesc::Capsule {
+        half_height: HALF_HEIGHT,
+        radius: RADIUS,
+    }
+}
+
+/// Expected mass and angular inertia of a single capsule: a cylinder and a ball.
+fn capsule_mass_properties() -> (f32, Matrix3<f32>) {
+    let r2 = RADIUS * RADIUS;
+    let h2 = HALF_HEIGHT * HALF_HEIGHT;
+    let cylinder = DENSITY * PI * r2 * 2.0 * HALF_HEIGHT;
+    let ball = DENSITY * 4.0 / 3.0 * PI * r2 * RADIUS;
+    let axial = cylinder * r2 / 2.0 + ball * r2 * 2.0 / 5.0;
+    let transverse = cylinder * (3.0 * r2 + 4.0 * h2) / 12.0
+        + ball * (r2 * 2.0 / 5.0 + h2 + HALF_HEIGHT * RADIUS * 3.0 / 4.0);
+    (
+        cylinder + ball,
+        Matrix3::from_diagonal(&Vector3::new(transverse, axial, transverse)),
+    )
+}
+
+fn assert_close(actual: f32, expected: f32, what: &str) {
+    assert!(
+        (actual - expected).abs() <= 1.0e-3 * expected.abs().max(1.0),
+        "{}: got {}, expected {}",
+        what,
+        actual,
+        expected
+    );
+}
+
+impl SimpleState for CapsuleMassState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let world = data.world;
+        world.add_resource(Gravity::zeros());
+
+        let collider = |shape| {
+            ColliderBuilder::from(shape)
+                .density(DENSITY)
+                .build()
+                .unwrap()
+        };
+
+        self.capsule = Some(
+            world
+                .create_entity()
+                .with(Transform::default())
+                .with(GlobalTransform::default())
+                .with(DynamicBody::new_rigidbody_from_colliders())
+                .with(collider(capsule()))
+                .build(),
+        );
+
+        self.compound = Some(
+            world
+                .create_entity()
+                .with(Transform::from(Vector3::new(10.0, 0.0, 0.0)))
+                .with(GlobalTransform::default())
+                .with(DynamicBody::new_rigidbody_from_colliders())
+                .with(collider(ShapeDesc::Compound {
+                    shapes: vec![
+                        (Isometry3::translation(-OFFSET, 0.0, 0.0), capsule()),
+                        (Isometry3::translation(OFFSET, 0.0, 0.0), capsule()),
+                    ],
+                }))
+                .build(),
+        );
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+
+        let bodies = data.world.read_storage::<DynamicBody>();
+        let handles = data.world.read_resource::<PhysicsHandles>();
+        let entities = [self.capsule.unwrap(), self.compound.unwrap()];
+        if entities
+            .iter()
+            .any(|entity| handles.colliders(*entity).is_empty())
+        {
+            assert!(
+                self.frames < MAX_FRAMES,
+                "Colliders weren't inserted after {} frames",
+                MAX_FRAMES
+            );
+            return Trans::None;
+        }
+
+        let (mass, angular_mass) = capsule_mass_properties();
+
+        let body = bodies.get(self.capsule.unwrap()).unwrap();
+        assert_close(body.mass, mass, "Capsule mass");
+        for i in 0..3 {
+            assert_close(
+                body.angular_mass[(i, i)],
+                angular_mass[(i, i)],
+                "Capsule angular inertia",
+            );
+        }
+
+        // Both capsules are moved away from the shared center of mass along `x`.
+        let body = bodies.get(self.compound.unwrap()).unwrap();
+        assert_close(body.mass, 2.0 * mass, "Compound mass");
+        assert_close(body.center_of_mass.x, 0.0, "Compound center of mass");
+        let shift = 2.0 * mass * OFFSET * OFFSET;
+        assert_close(
+            body.angular_mass[(0, 0)],
+            2.0 * angular_mass[(0, 0)],
+            "Compound angular inertia around x",
+        );
+        assert_close(
+            body.angular_mass[(1, 1)],
+            2.0 * angular_mass[(1, 1)] + shift,
+            "Compound angular inertia around y",
+        );
+        assert_close(
+            body.angular_mass[(2, 2)],
+            2.0 * angular_mass[(2, 2)] + shift,
+            "Compound angular inertia around z",
+        );
+
+        Trans::Quit
+    }
+}
+
+#[test]
+fn capsule_colliders_give_mass_to_their_body() {
+    common::run(CapsuleMassState::default(), common::physics_game_data());
+}
@@ -0,0 +1,89 @@
+//! A body launched at orbital speed around a radial gravity field, without global gravity, stays
+//! on a circular orbit.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::Vector3;
+use amethyst::core::{GlobalTransform, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point, Velocity};
+use nphysics_ecs_dumb::*;
+
+const STRENGTH: f32 = 100.0;
+const RADIUS: f32 = 10.0;
+/// Largest distance from `RADIUS` allowed by the integration error. Without the field, the body
+/// moves off in a straight line and leaves the band about a second in.
+const TOLERANCE: f32 = 0.05 * RADIUS;
+const FRAMES: u32 = 600;
+
+#[derive(Default)]
+struct OrbitState {
+    frames: u32,
+    body: Option<Entity>,
+}
+
+impl SimpleState for OrbitState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        data.world.add_resource(Gravity::zeros());
+
+        data.world
+            .create_entity()
+            .with(Transform::default())
+            .with(GlobalTransform::default())
+            .with(GravityField::new(GravityFieldKind::Radial {
+                strength: STRENGTH,
+                falloff: 2.0,
+                min_distance: 0.5,
+            }))
+            .build();
+
+        // The field pulls with an acceleration of `STRENGTH / distance²`, balanced on a circle by
+        // a speed of `sqrt(STRENGTH / distance)`.
+        let speed = (STRENGTH / RADIUS).sqrt();
+        let body = data
+            .world
+            .create_entity()
+            .with(Transform::from(Vector3::new(RADIUS, 0.0, 0.0)))
+            .with(GlobalTransform::default())
+            .with(DynamicBody::new_rigidbody_with_velocity(
+                Velocity::linear(0.0, 0.0, speed),
+                1.0,
+                AngularInertia::identity(),
+                Point::origin(),
+            ))
+            .build();
+        self.body = Some(body);
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+
+        let transforms = data.world.read_storage::<Transform>();
+        let distance = transforms
+            .get(self.body.unwrap())
+            .expect("Orbiting body lost its transform")
+            .translation()
+            .norm();
+        assert!(
+            (distance - RADIUS).abs() <= TOLERANCE,
+            "Body left its orbit on frame {}: distance {} to the attractor, expected {} ± {}",
+            self.frames,
+            distance,
+            RADIUS,
+            TOLERANCE
+        );
+
+        if self.frames < FRAMES {
+            Trans::None
+        } else {
+            Trans::Quit
+        }
+    }
+}
+
+#[test]
+fn body_orbits_radial_gravity_field() {
+    common::run(OrbitState::default(), common::physics_game_data());
+}
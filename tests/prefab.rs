@@ -0,0 +1,121 @@
+//! Loads `examples/assets/prefab/physics.ron` headlessly and checks the spawned components.
+
+mod common;
+
+use amethyst::assets::{PrefabLoader, PrefabLoaderSystem, RonFormat};
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Join;
+use amethyst::core::math::Vector3;
+use amethyst::core::{Transform, TransformBundle};
+use amethyst::derive::PrefabData;
+use amethyst::{GameData, GameDataBuilder, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::*;
+use serde::Deserialize;
+
+/// Frames to wait for the prefab to load and its colliders to be inserted in the physics world.
+const MAX_FRAMES: u32 = 300;
+
+#[derive(Default, Deserialize, PrefabData)]
+#[serde(default)]
+struct ScenePrefabData {
+    transform: Option<Transform>,
+    physics: Option<PhysicsPrefab>,
+}
+
+#[derive(Default)]
+struct CheckPrefabState {
+    frames: u32,
+}
+
+impl SimpleState for CheckPrefabState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let handle = data
+            .world
+            .exec(|loader: PrefabLoader<'_, ScenePrefabData>| {
+                loader.load("prefab/physics.ron", RonFormat, (), ())
+            });
+        data.world.create_entity().with(handle).build();
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.frames += 1;
+
+        let entities = data.world.entities();
+        let bodies = data.world.read_storage::<DynamicBody>();
+        let colliders = data.world.read_storage::<Collider>();
+        let handles = data.world.read_resource::<PhysicsHandles>();
+
+        let loaded = (&entities, (&bodies).maybe(), &colliders)
+            .join()
+            .collect::<Vec<_>>();
+        let inserted = loaded.len() == 2
+            && loaded
+                .iter()
+                .all(|(entity, _, _)| !handles.colliders(*entity).is_empty());
+        if !inserted {
+            assert!(
+                self.frames < MAX_FRAMES,
+                "Prefab entities weren't loaded and inserted after {} frames",
+                MAX_FRAMES
+            );
+            return Trans::None;
+        }
+
+        let (_, body, collider) = loaded
+            .iter()
+            .find(|(_, body, _)| body.is_some())
+            .expect("Missing the falling crate");
+        let body = body.unwrap();
+        assert_eq!(body.mass_source, MassSource::FromColliders);
+        // A 1x1x1 cuboid of density 2.
+        assert!(
+            (body.mass - 2.0).abs() < 1.0e-4,
+            "Mass not computed from the collider: {}",
+            body.mass
+        );
+        assert_eq!(collider.density, 2.0);
+        assert_eq!(
+            collider.material_preset.as_ref().map(String::as_str),
+            Some("rubber")
+        );
+        match &collider.shape {
+            ShapeDesc::Cuboid { half_extents } => {
+                assert_eq!(*half_extents, Vector3::new(0.5, 0.5, 0.5))
+            }
+            shape => panic!("Unexpected crate shape: {:?}", shape),
+        }
+
+        let (_, _, ground) = loaded
+            .iter()
+            .find(|(_, body, _)| body.is_none())
+            .expect("Missing the ground");
+        assert_eq!(
+            ground.material_preset.as_ref().map(String::as_str),
+            Some("metal")
+        );
+        match &ground.shape {
+            ShapeDesc::Cuboid { half_extents } => {
+                assert_eq!(*half_extents, Vector3::new(10.0, 1.0, 10.0))
+            }
+            shape => panic!("Unexpected ground shape: {:?}", shape),
+        }
+
+        Trans::Quit
+    }
+}
+
+#[test]
+fn prefab_spawns_bodies_and_colliders() {
+    let game_data = GameDataBuilder::default()
+        .with(
+            PrefabLoaderSystem::<ScenePrefabData>::default(),
+            "scene_loader",
+            &[],
+        )
+        .with_bundle(TransformBundle::new().with_dep(&["scene_loader"]))
+        .unwrap()
+        .with_bundle(PhysicsBundle::new().with_dep(&["transform_system"]))
+        .unwrap();
+
+    common::run(CheckPrefabState::default(), game_data);
+}
@@ -0,0 +1,95 @@
+//! `ShapeDesc` survives a round trip through RON, and rejects heightfields with a non-positive
+//! scale.
+
+use amethyst::core::math::{Isometry3, Point3, Vector3};
+use nphysics_ecs_dumb::*;
+
+/// One shape of every serializable variant.
+fn shapes() -> Vec<ShapeDesc> {
+    let tetrahedron = vec![
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.0, 0.0, 0.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Point3::new(0.0, 0.0, 1.0),
+    ];
+    vec![
+        ShapeDesc::Ball { radius: 0.5 },
+        ShapeDesc::Cuboid {
+            half_extents: Vector3::new(1.0, 2.0, 3.0),
+        },
+        ShapeDesc::Capsule {
+            half_height: 1.0,
+            radius: 0.5,
+        },
+        ShapeDesc::Cylinder {
+            half_height: 1.0,
+            radius: 0.5,
+        },
+        ShapeDesc::Cone {
+            half_height: 1.0,
+            radius: 0.5,
+        },
+        ShapeDesc::ConvexHull {
+            points: tetrahedron.clone(),
+        },
+        ShapeDesc::TriMesh {
+            points: tetrahedron,
+            indices: vec![Point3::new(0, 1, 2), Point3::new(0, 1, 3)],
+        },
+        ShapeDesc::HeightField {
+            heights: vec![vec![0.0, 1.0, 0.0], vec![1.0, 2.0, 1.0]],
+            scale: Vector3::new(10.0, 1.0, 10.0),
+        },
+        ShapeDesc::Compound {
+            shapes: vec![
+                (
+                    Isometry3::translation(-1.0, 0.0, 0.0),
+                    ShapeDesc::Ball { radius: 0.5 },
+                ),
+                (
+                    Isometry3::translation(1.0, 0.0, 0.0),
+                    ShapeDesc::Cuboid {
+                        half_extents: Vector3::repeat(0.5),
+                    },
+                ),
+            ],
+        },
+    ]
+}
+
+#[test]
+fn shape_desc_ron_round_trip() {
+    for shape in shapes() {
+        let serialized = ron::ser::to_string(&shape).expect("Failed to serialize the shape");
+        let deserialized: ShapeDesc = ron::de::from_str(&serialized)
+            .unwrap_or_else(|error| panic!("Failed to deserialize {}: {}", serialized, error));
+
+        assert_eq!(format!("{:?}", deserialized), format!("{:?}", shape));
+        assert_eq!(
+            ron::ser::to_string(&deserialized).expect("Failed to serialize the shape again"),
+            serialized
+        );
+        if let Err(error) = deserialized.build() {
+            panic!("Failed to build deserialized {:?}: {}", deserialized, error);
+        }
+    }
+}
+
+#[test]
+fn heightfield_scale_must_be_positive() {
+    for scale in &[
+        Vector3::new(0.0, 1.0, 1.0),
+        Vector3::new(1.0, -1.0, 1.0),
+        Vector3::new(1.0, 1.0, 0.0),
+    ] {
+        let shape = ShapeDesc::HeightField {
+            heights: vec![vec![0.0, 0.0], vec![0.0, 0.0]],
+            scale: *scale,
+        };
+        match shape.build() {
+            Err(ShapeDescError::NonPositiveDimension("scale", _)) => {}
+            Err(error) => panic!("Heightfield with scale {} failed with: {}", scale, error),
+            Ok(_) => panic!("Heightfield with scale {} was built", scale),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! A `Contacts` component inserted on an entity already overlapping a trigger volume reports the
+//! volume, until the entity leaves it.
+
+mod common;
+
+use amethyst::core::ecs::world::Builder;
+use amethyst::core::ecs::Entity;
+use amethyst::core::math::Vector3;
+use amethyst::core::shrev::{EventChannel, ReaderId};
+use amethyst::core::{GlobalTransform, Time, Transform};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, Trans};
+use nphysics_ecs_dumb::ncollide::query::Proximity;
+use nphysics_ecs_dumb::nphysics::math::{AngularInertia, Point, Velocity};
+use nphysics_ecs_dumb::*;
+
+/// Half extents of the trigger volume, centered on the origin.
+const VOLUME_HALF_EXTENT: f32 = 2.0;
+const BALL_RADIUS: f32 = 0.5;
+const BALL_SPEED: f32 = 4.0;
+/// Seconds for the ball to leave the volume, with some margin.
+const MAX_SECONDS: f64 = 5.0;
+
+#[derive(Default)]
+struct TouchingState {
+    reader: Option<ReaderId<EntityProximityEvent>>,
+    volume: Option<Entity>,
+    ball: Option<Entity>,
+    contacts_inserted: bool,
+    entered: bool,
+    exited: bool,
+}
+
+impl SimpleState for TouchingState {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let world = data.world;
+        world.add_resource(Gravity::zeros());
+        self.reader = Some(
+            world
+                .write_resource::<EventChannel<EntityProximityEvent>>()
+                .register_reader(),
+        );
+
+        self.volume = Some(
+            world
+                .create_entity()
+                .with(Transform::default())
+                .with(GlobalTransform::default())
+                .with(
+                    ColliderBuilder::from(ShapeDesc::Cuboid {
+                        half_extents: Vector3::repeat(VOLUME_HALF_EXTENT),
+                    })
+                    .trigger()
+                    .build()
+                    .unwrap(),
+                )
+                .build(),
+        );
+
+        // Starts inside the volume, moving out of it.
+        self.ball = Some(
+            world
+                .create_entity()
+                .with(Transform::default())
+                .with(GlobalTransform::default())
+                .with(DynamicBody::new_rigidbody_with_velocity(
+                    Velocity::linear(BALL_SPEED, 0.0, 0.0),
+                    1.0,
+                    AngularInertia::identity(),
+                    Point::origin(),
+                ))
+                .with(
+                    ColliderBuilder::from(ShapeDesc::Ball {
+                        radius: BALL_RADIUS,
+                    })
+                    .build()
+                    .unwrap(),
+                )
+                .build(),
+        );
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        assert!(
+            data.world.read_resource::<Time>().absolute_time_seconds() < MAX_SECONDS,
+            "Ball didn't leave the volume after {} seconds",
+            MAX_SECONDS
+        );
+        let ball = self.ball.unwrap();
+        let volume = self.volume.unwrap();
+
+        if !self.contacts_inserted {
+            let started = data
+                .world
+                .read_resource::<EventChannel<EntityProximityEvent>>()
+                .read(self.reader.as_mut().unwrap())
+                .any(|(entity1, entity2, event)| {
+                    event.new_status == Proximity::Intersecting
+                        && (*entity1 == ball || *entity2 == ball)
+                });
+            if started {
+                // The overlap started already, no event reports it to the new component.
+                data.world
+                    .write_storage::<Contacts>()
+                    .insert(ball, Contacts::new())
+                    .unwrap();
+                self.contacts_inserted = true;
+            }
+            return Trans::None;
+        }
+
+        let all_contacts = data.world.read_storage::<Contacts>();
+        let contacts = all_contacts.get(ball).unwrap();
+        self.entered |= contacts.entered().contains(&volume);
+        self.exited |= contacts.exited().contains(&volume);
+
+        let x = data
+            .world
+            .read_storage::<Transform>()
+            .get(ball)
+            .unwrap()
+            .translation()
+            .x;
+        if x < VOLUME_HALF_EXTENT + 2.0 * BALL_RADIUS {
+            if !self.exited {
+                assert!(
+                    contacts.is_touching(volume),
+                    "Contacts inserted inside the volume doesn't report it"
+                );
+            }
+            return Trans::None;
+        }
+
+        assert!(self.entered, "The volume was never reported as entered");
+        assert!(self.exited, "The volume was never reported as exited");
+        assert!(!contacts.is_touching(volume));
+        Trans::Quit
+    }
+}
+
+#[test]
+fn contacts_inserted_inside_a_volume_report_it() {
+    common::run(TouchingState::default(), common::physics_game_data());
+}